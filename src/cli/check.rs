@@ -0,0 +1,63 @@
+use std::{error::Error, path::Path};
+
+use crate::core::feusic::{
+    validation::{InvalidFeusicError, Severity, ValidationIssue},
+    Feusic,
+};
+
+use super::feusic_paths;
+
+/// `feusic check <path>`: validates a `.feusic` zip file, a `.feusic` folder or all the
+/// feusics of a playlist folder, exiting with a non-zero code when any of them has errors.
+pub fn check(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args
+        .first()
+        .ok_or("check needs the path of a .feusic file, a .feusic folder or a playlist folder")?;
+
+    let feusic_paths = feusic_paths(Path::new(path))?;
+    let mut feusics_with_errors = 0;
+
+    for feusic_path in feusic_paths.iter() {
+        let issues = match Feusic::from_feusic_path(feusic_path) {
+            Ok(feusic) => feusic.validate(),
+            Err(e) => match e.downcast::<InvalidFeusicError>() {
+                Ok(invalid_feusic) => invalid_feusic.issues,
+                Err(e) => vec![ValidationIssue::error(e.to_string())],
+            },
+        };
+
+        let errors = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            feusics_with_errors += 1;
+        }
+
+        if issues.is_empty() {
+            println!("{}: ok", feusic_path.display());
+        } else {
+            println!(
+                "{}: {} errors, {} warnings",
+                feusic_path.display(),
+                errors,
+                issues.len() - errors
+            );
+            for issue in issues {
+                println!("  {}", issue);
+            }
+        }
+    }
+
+    println!(
+        "Checked {} feusics, {} with errors",
+        feusic_paths.len(),
+        feusics_with_errors
+    );
+
+    if feusics_with_errors > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use std::{error::Error, path::Path};
+
+use crate::core::feusic::{export::GraphFormat, Feusic};
+
+/// `feusic graph <path> [--format dot|mermaid]`: prints the musics of a `.feusic` zip file or
+/// folder and their transitions as a graph, by default in the DOT format of Graphviz.
+pub fn graph(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args
+        .first()
+        .ok_or("graph needs the path of a .feusic file or a .feusic folder")?;
+
+    let mut format = GraphFormat::Dot;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--format" => {
+                format = options
+                    .next()
+                    .ok_or("--format needs dot or mermaid")?
+                    .parse()?;
+            }
+            _ => return Err(format!("unknown option {}", option).into()),
+        }
+    }
+
+    let feusic =
+        Feusic::from_feusic_path(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+    println!("{}", feusic.to_graph(format));
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, path::Path};
+
+use crate::core::feusic::{
+    audio_files_names,
+    format::{migrate_feusic_toml, FORMAT_VERSION},
+    read_feusic_toml, write_feusic_toml,
+};
+
+use super::feusic_paths;
+
+/// `feusic migrate <path>`: rewrites the feusic.toml of a `.feusic` zip file, a `.feusic`
+/// folder or all the feusics of a playlist folder to the latest format version, in place.
+pub fn migrate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args
+        .first()
+        .ok_or("migrate needs the path of a .feusic file, a .feusic folder or a playlist folder")?;
+
+    let feusic_paths = feusic_paths(Path::new(path))?;
+    let mut migrated = 0;
+
+    for feusic_path in feusic_paths.iter() {
+        let (feusic_toml, files_names) = read_feusic_toml(feusic_path)?;
+
+        match migrate_feusic_toml(&feusic_toml, &audio_files_names(&files_names))
+            .map_err(|e| format!("{}: {}", feusic_path.display(), e))?
+        {
+            Some(migrated_toml) => {
+                write_feusic_toml(feusic_path, &migrated_toml)?;
+                migrated += 1;
+                println!(
+                    "{}: migrated to format_version {}",
+                    feusic_path.display(),
+                    FORMAT_VERSION
+                );
+            }
+            None => println!("{}: already up to date", feusic_path.display()),
+        }
+    }
+
+    println!("Migrated {} of {} feusics", migrated, feusic_paths.len());
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub mod check;
+pub mod graph;
+pub mod migrate;
+pub mod normalize;
+pub mod pack;
+pub mod simulate;
+pub mod unpack;
+
+/// Returns the path itself if it is a `.feusic` zip file or folder, otherwise all the feusics
+/// in the playlist folder it points to.
+fn feusic_paths(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if is_feusic(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    if !path.is_dir() {
+        return Err(format!("{:?} is neither a feusic nor a playlist folder", path).into());
+    }
+
+    let mut paths = fs::read_dir(path)
+        .map_err(|e| format!("Folder path should exist. {}", e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_feusic(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Reads the `-o <path>` option of the commands that write a feusic somewhere else.
+fn output_option(options: &[String]) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let mut output = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "-o" | "--output" => {
+                output = Some(PathBuf::from(
+                    options.next().ok_or("-o needs the path to write to")?,
+                ));
+            }
+            _ => return Err(format!("unknown option {}", option).into()),
+        }
+    }
+
+    Ok(output)
+}
+
+fn is_feusic(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "feusic")
+}
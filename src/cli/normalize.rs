@@ -0,0 +1,79 @@
+use std::{error::Error, path::Path};
+
+use crate::core::feusic::{
+    format::set_music_gains, loudness::measure_loudness, read_feusic_toml, write_feusic_toml,
+    Feusic,
+};
+
+use super::feusic_paths;
+
+/// `feusic normalize <path> [--target <LUFS>] [--dry-run]`: measures the integrated loudness
+/// of every music of the feusics and writes the `gain_db` that brings them to the target.
+/// Without a target the musics of a feusic are brought to its quietest one, so no music
+/// gets louder than it was.
+pub fn normalize(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args.first().ok_or(
+        "normalize needs the path of a .feusic file, a .feusic folder or a playlist folder",
+    )?;
+
+    let mut target = None;
+    let mut dry_run = false;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--target" => {
+                let value = options.next().ok_or("--target needs a loudness in LUFS")?;
+                target = Some(
+                    value
+                        .parse::<f64>()
+                        .map_err(|e| format!("--target {}: {}", value, e))?,
+                );
+            }
+            "--dry-run" => dry_run = true,
+            _ => return Err(format!("unknown option {}", option).into()),
+        }
+    }
+
+    for feusic_path in feusic_paths(Path::new(path))? {
+        let feusic = Feusic::from_feusic_path(&feusic_path)?;
+        println!("{}", feusic_path.display());
+
+        let loudnesses = feusic
+            .musics
+            .iter()
+            .map(|music| measure_loudness(&music.loader, &music.name))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}: {}", feusic_path.display(), e))?;
+
+        let Some(target) = target.or_else(|| loudnesses.iter().flatten().copied().reduce(f64::min))
+        else {
+            println!("  no music to measure");
+            continue;
+        };
+
+        let gains_db = loudnesses
+            .iter()
+            .map(|loudness| loudness.map(|loudness| ((target - loudness) * 10.0).round() / 10.0))
+            .collect::<Vec<_>>();
+
+        for ((music, loudness), gain_db) in feusic.musics.iter().zip(&loudnesses).zip(&gains_db) {
+            match (loudness, gain_db) {
+                (Some(loudness), Some(gain_db)) => println!(
+                    "  {}: {:.1} LUFS, gain_db {:.1}",
+                    music.name, loudness, gain_db
+                ),
+                _ => println!("  {}: silent, gain_db unchanged", music.name),
+            }
+        }
+
+        if !dry_run {
+            let (feusic_toml, files_names) = read_feusic_toml(&feusic_path)?;
+            write_feusic_toml(
+                &feusic_path,
+                &set_music_gains(&feusic_toml, &files_names, &gains_db)?,
+            )?;
+        }
+    }
+
+    Ok(())
+}
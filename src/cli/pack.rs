@@ -0,0 +1,181 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+use crate::core::feusic::{folder_files_names, loader::is_supported_audio_file, Feusic};
+
+use super::output_option;
+
+/// `feusic pack <folder> [-o out.feusic]`: validates a `.feusic` folder and writes its files
+/// into a `.feusic` zip file, by default next to the folder with `.feusic` appended to its
+/// name, e.g. `song.feusic` is packed into `song.feusic.feusic`. Like `unpack` an existing
+/// output is never overwritten. Packing the same files always gives the same zip: entries are
+/// sorted by name, have a fixed modification time and audio files are stored uncompressed.
+pub fn pack(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let folder = PathBuf::from(
+        args.first()
+            .ok_or("pack needs the path of a .feusic folder")?,
+    );
+    let output = match output_option(&args[1..])? {
+        Some(output) => output,
+        None => {
+            let mut output = folder.clone().into_os_string();
+            output.push(".feusic");
+            PathBuf::from(output)
+        }
+    };
+
+    if !folder.is_dir() {
+        return Err(format!("{} is not a folder", folder.display()).into());
+    }
+    if output.exists() {
+        return Err(format!(
+            "{} already exists, choose another file with -o",
+            output.display()
+        )
+        .into());
+    }
+
+    Feusic::from_feusic_folder(&folder).map_err(|e| format!("{}: {}", folder.display(), e))?;
+
+    let files_names = folder_files_names(&folder)?;
+    // Written next to the output and then moved over it, so a failure leaves no broken zip.
+    let temp_path = output.with_extension("feusic.tmp");
+    if let Err(e) = write_zip(&folder, &files_names, &temp_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    fs::rename(&temp_path, &output)?;
+
+    Feusic::from_feusic_path(&output).map_err(|e| format!("{}: {}", output.display(), e))?;
+
+    println!(
+        "Packed {} files of {} into {}",
+        files_names.len(),
+        folder.display(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn write_zip(folder: &Path, files_names: &[String], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = ZipWriter::new(File::create(path)?);
+
+    for file_name in files_names {
+        // Audio files are already compressed, storing them lets the player stream them.
+        let compression_method = if is_supported_audio_file(Path::new(file_name)) {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        let options = SimpleFileOptions::default()
+            .compression_method(compression_method)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644);
+
+        writer.start_file(file_name.as_str(), options)?;
+        io::copy(&mut File::open(folder.join(file_name))?, &mut writer)?;
+    }
+
+    writer.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, process};
+
+    use crate::cli::unpack::unpack;
+
+    use super::*;
+
+    /// Empty folder in the temporary folder of the system, removed when dropped.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new(name: &str) -> TempFolder {
+            let path = env::temp_dir().join(format!("feusic-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(path)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes a feusic with two musics into `folder`.
+    fn feusic_folder(folder: &Path) -> PathBuf {
+        fs::create_dir(folder).unwrap();
+        fs::write(
+            folder.join("feusic.toml"),
+            "timing = \"s0|0:w1000:1|1:w1000:0\"\nduration = 600",
+        )
+        .unwrap();
+        fs::write(folder.join("a.mp3"), [1, 2, 3]).unwrap();
+        fs::write(folder.join("b.mp3"), [4, 5, 6]).unwrap();
+        folder.to_path_buf()
+    }
+
+    fn args(args: &[&Path]) -> Vec<String> {
+        args.iter()
+            .map(|arg| arg.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn packed_folder_unpacks_to_the_same_files() {
+        let temp = TempFolder::new("pack");
+        let folder = feusic_folder(&temp.0.join("song"));
+
+        pack(&args(&[&folder])).unwrap();
+        let zip = temp.0.join("song.feusic");
+        assert!(zip.is_file());
+        assert!(!temp.0.join("song.feusic.tmp").exists());
+
+        let error = pack(&args(&[&folder])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{} already exists, choose another file with -o",
+                zip.display()
+            )
+        );
+
+        let unpacked = temp.0.join("unpacked");
+        unpack(&args(&[&zip, Path::new("-o"), &unpacked])).unwrap();
+
+        let files_names = folder_files_names(&folder).unwrap();
+        assert_eq!(folder_files_names(&unpacked).unwrap(), files_names);
+        for file_name in files_names {
+            assert_eq!(
+                fs::read(unpacked.join(&file_name)).unwrap(),
+                fs::read(folder.join(&file_name)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn dotted_and_feusic_folders_keep_their_name() {
+        let temp = TempFolder::new("pack-dotted-folders");
+
+        for name in ["boss.v2", "song.feusic"] {
+            let folder = feusic_folder(&temp.0.join(name));
+
+            pack(&args(&[&folder])).unwrap();
+
+            assert!(temp.0.join(format!("{}.feusic", name)).is_file());
+            assert!(folder.is_dir());
+        }
+    }
+}
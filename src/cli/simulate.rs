@@ -0,0 +1,188 @@
+use std::{error::Error, path::Path, time::Duration};
+
+use rand::{random, rngs::StdRng, SeedableRng};
+
+use crate::core::feusic::{decode::measure_duration, timing::WaitUnit, Feusic};
+
+/// `feusic simulate <path> [--seed <N>] [--duration <2h>]`: walks the transitions of a
+/// `.feusic` zip file or folder with a virtual clock, without playing it, and prints every
+/// transition taken with the time spent in each music. The same seed always gives the same
+/// output, without one a random seed is used and printed. The duration defaults to the one
+/// of the feusic.
+pub fn simulate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args
+        .first()
+        .ok_or("simulate needs the path of a .feusic file or a .feusic folder")?;
+
+    let mut seed = None;
+    let mut duration = None;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--seed" => {
+                let value = options.next().ok_or("--seed needs a number")?;
+                seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|e| format!("--seed {}: {}", value, e))?,
+                );
+            }
+            "--duration" => {
+                let value = options
+                    .next()
+                    .ok_or("--duration needs a duration, e.g. 2h")?;
+                duration = Some(
+                    parse_duration(value).map_err(|e| format!("--duration {}: {}", value, e))?,
+                );
+            }
+            _ => return Err(format!("unknown option {}", option).into()),
+        }
+    }
+
+    let feusic =
+        Feusic::from_feusic_path(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+    let duration = duration
+        .or_else(|| feusic.looping.duration())
+        .ok_or("the feusic has no duration, set one with --duration")?;
+    if duration.is_zero() {
+        return Err("the simulation needs a duration greater than 0".into());
+    }
+    let seed = seed.unwrap_or_else(random);
+
+    // Only the waits in loops and the bars the transitions wait for need the durations of
+    // the musics, they are not read otherwise.
+    let has_loop_waits = feusic
+        .transitions()
+        .iter()
+        .flatten()
+        .any(|next| next.wait.0.unit == WaitUnit::Loops || next.wait.1.unit == WaitUnit::Loops);
+    let musics_durations = if has_loop_waits || feusic.tempo.is_some() {
+        feusic
+            .musics
+            .iter()
+            .map(|music| measure_duration(&music.loader, &music.name))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}: {}", path, e))?
+    } else {
+        vec![]
+    };
+
+    let simulation = feusic.simulate(
+        duration,
+        &musics_durations,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    let titles = feusic.node_titles();
+
+    println!(
+        "{}: {} simulated with seed {}",
+        feusic.display_name(),
+        format_duration(simulation.duration),
+        seed
+    );
+    println!(
+        "  {}  start {}",
+        format_timestamp(Duration::ZERO),
+        titles[simulation.start]
+    );
+    for transition in simulation.transitions.iter() {
+        println!(
+            "  {}  {} -> {}, waited {}",
+            format_timestamp(transition.at),
+            titles[transition.from],
+            titles[transition.to],
+            format_duration(transition.wait)
+        );
+    }
+
+    println!(
+        "Time per {}",
+        if feusic.is_layered() {
+            "state"
+        } else {
+            "music"
+        }
+    );
+    for (title, time) in titles.iter().zip(simulation.time_per_node.iter()) {
+        println!(
+            "  {}: {} ({:.1}%)",
+            title,
+            format_duration(*time),
+            time.as_secs_f64() * 100.0 / simulation.duration.as_secs_f64()
+        );
+    }
+
+    println!("Transitions: {}", simulation.transitions.len());
+    for ((from, to), count) in simulation.transition_counts() {
+        println!("  {} -> {}: {}", titles[from], titles[to], count);
+    }
+
+    Ok(())
+}
+
+/// Parses durations like `2h`, `90m`, `1h30m`, `45s` or `500ms`, a number alone is in seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut duration = Duration::ZERO;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or("the last number has no unit")?;
+        if digits == 0 {
+            return Err("expected a number".to_string());
+        }
+        let amount = rest[..digits].parse::<u64>().map_err(|e| e.to_string())?;
+        rest = &rest[digits..];
+
+        let unit_length = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit_length] {
+            "h" => amount.checked_mul(3600).map(Duration::from_secs),
+            "m" => amount.checked_mul(60).map(Duration::from_secs),
+            "s" => Some(Duration::from_secs(amount)),
+            "ms" => Some(Duration::from_millis(amount)),
+            unit => return Err(format!("unknown unit {}, expected h, m, s or ms", unit)),
+        };
+        duration = part
+            .and_then(|part| duration.checked_add(part))
+            .ok_or("the duration is too large")?;
+        rest = &rest[unit_length..];
+    }
+
+    Ok(duration)
+}
+
+/// Time since the start of the feusic, e.g. `01:02:03.456`.
+fn format_timestamp(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        time.subsec_millis()
+    )
+}
+
+/// Duration in its biggest units, e.g. `1h2m3s` or `1.500s`.
+fn format_duration(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let mut formatted = String::new();
+    if seconds >= 3600 {
+        formatted += &format!("{}h", seconds / 3600);
+    }
+    if seconds % 3600 >= 60 {
+        formatted += &format!("{}m", seconds / 60 % 60);
+    }
+    match (seconds % 60, time.subsec_millis()) {
+        (0, 0) if !formatted.is_empty() => {}
+        (seconds, 0) => formatted += &format!("{}s", seconds),
+        (seconds, millis) => formatted += &format!("{}.{:03}s", seconds, millis),
+    }
+    formatted
+}
@@ -0,0 +1,70 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io,
+    path::PathBuf,
+};
+
+use zip::ZipArchive;
+
+use crate::core::feusic::Feusic;
+
+use super::output_option;
+
+/// `feusic unpack <file.feusic> [-o folder]`: validates a `.feusic` zip file and extracts its
+/// files into a folder, by default next to the file with the same name without extension.
+pub fn unpack(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let file = PathBuf::from(
+        args.first()
+            .ok_or("unpack needs the path of a .feusic file")?,
+    );
+    let output = output_option(&args[1..])?.unwrap_or_else(|| file.with_extension(""));
+
+    if !file.is_file() {
+        return Err(format!("{} is not a file", file.display()).into());
+    }
+    if output.exists() {
+        return Err(format!(
+            "{} already exists, choose another folder with -o",
+            output.display()
+        )
+        .into());
+    }
+
+    Feusic::from_feusic_path(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    let mut zip = ZipArchive::new(File::open(&file)?)?;
+    fs::create_dir_all(&output)?;
+
+    let mut unpacked = 0;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let name = entry.enclosed_name().ok_or_else(|| {
+            format!(
+                "{}: {} points outside of the feusic",
+                file.display(),
+                entry.name()
+            )
+        })?;
+        let path = output.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+        unpacked += 1;
+    }
+
+    println!(
+        "Unpacked {} files of {} into {}",
+        unpacked,
+        file.display(),
+        output.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,1562 @@
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    format::{self, FORMAT_VERSION},
+    names::{MusicNames, MusicRef},
+    parameters::{Condition, Parameter},
+    selection::Constraints,
+    tempo::{Quantize, Tempo},
+    timing::{
+        InvalidTimingError, ParsedTiming, ParsedTimingChoice, ParsedTimingMusic, Wait, WaitUnit,
+    },
+    Fade, FadeEasing, Feusic, FeusicMeta, LayerState, LoopRegion, Looping, Next, Start, Stinger,
+    DEFAULT_FADE_DURATION,
+};
+
+/// Keys of a table that are not part of the feusic.toml format, they are ignored before
+/// [`format::STRICT_FORMAT_VERSION`] and rejected from it.
+pub(super) type UnknownKeys = BTreeMap<String, toml::Value>;
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct FeusicConfig {
+    #[serde(default)]
+    pub format_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musics: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intro: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ending: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ending_fade: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<StartConfig>,
+    pub duration: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_start: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_end: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade_easing: Option<FadeEasing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beats_per_bar: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beat_offset: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantize: Option<Quantize>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, ParameterConfig>,
+    #[serde(default, skip_serializing_if = "is_default_meta")]
+    pub meta: FeusicMeta,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub music: Vec<MusicConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<StateConfig>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: UnknownKeys,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct MusicConfig {
+    pub index: MusicRef,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_start: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loop_end: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain_db: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ending: Option<String>,
+    /// Limits on the transitions to the music that do not define their own.
+    #[serde(flatten)]
+    pub constraints: ConstraintsConfig,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shuffle: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next: Vec<NextConfig>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: UnknownKeys,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct NextConfig {
+    pub target: MusicRef,
+    pub wait_min: WaitConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_max: Option<WaitConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub easing: Option<FadeEasing>,
+    /// File name of the stinger played when the transition starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stinger: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duck_db: Option<f64>,
+    /// Condition on the `[parameters]` for the transition to happen, e.g. `intensity >= 0.7`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(flatten)]
+    pub constraints: ConstraintsConfig,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: UnknownKeys,
+}
+
+/// The music or state a feusic starts from, or a table of the ones it can start from with their
+/// probability weight, e.g. `start = { map = 70, town = 30 }`.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub(super) enum StartConfig {
+    Single(MusicRef),
+    Weighted(BTreeMap<String, usize>),
+}
+
+/// A wait in milliseconds, or a string with a unit like in the `timing` string, e.g. `"2m"`.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub(super) enum WaitConfig {
+    Millis(usize),
+    WithUnit(String),
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub(super) struct ConstraintsConfig {
+    /// Milliseconds since the target was last entered before the transition can happen again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown_transitions: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_consecutive: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct ParameterConfig {
+    #[serde(default)]
+    pub min: f64,
+    #[serde(default = "default_parameter_max")]
+    pub max: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<f64>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: UnknownKeys,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct StateConfig {
+    pub name: String,
+    /// Volume in dB of the musics playing in the state, by music name or index.
+    #[serde(default)]
+    pub layers: BTreeMap<String, f64>,
+    /// Limits on the transitions to the state that do not define their own.
+    #[serde(flatten)]
+    pub constraints: ConstraintsConfig,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shuffle: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub next: Vec<NextConfig>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: UnknownKeys,
+}
+
+fn prefixed_keys<'a>(
+    prefix: &'a str,
+    unknown: &'a UnknownKeys,
+) -> impl Iterator<Item = String> + 'a {
+    unknown.keys().map(move |key| format!("{}{}", prefix, key))
+}
+
+/// A `[[music]]` table with the name used to point at it in errors.
+pub(super) struct MusicTable<'a> {
+    pub name: String,
+    pub config: &'a MusicConfig,
+}
+
+/// The `[[state]]` tables of a layered feusic.
+pub(super) struct LayerStates {
+    pub start: Vec<Start>,
+    pub states: Vec<LayerState>,
+}
+
+impl<M> Feusic<M> {
+    /// Serializes the feusic into a `feusic.toml` with its transitions defined as
+    /// `[[music]]` tables. Loading it back gives the same musics transitions.
+    pub fn to_feusic_toml(&self) -> Result<String, Box<dyn Error>> {
+        let (duration, loop_start, loop_end) = match self.looping {
+            Looping::Whole(duration) => (duration, None, None),
+            Looping::Partial {
+                duration,
+                start,
+                end,
+            } => (duration, Some(start), Some(end)),
+            Looping::None => return Err("Cannot serialize a feusic without duration".into()),
+        };
+
+        let has_transitions =
+            self.is_layered() || self.musics.iter().any(|m| !m.next_choices.is_empty());
+
+        let music = self
+            .musics
+            .iter()
+            .enumerate()
+            .filter(|(_, music)| {
+                !music.next_choices.is_empty()
+                    || music.loop_region.is_some()
+                    || music.title.is_some()
+                    || music.gain_db != 0.0
+                    || music.ending.is_some()
+                    || music.shuffle
+            })
+            .map(|(index, music)| MusicConfig {
+                index: MusicRef::Index(index),
+                title: music.title.clone(),
+                loop_start: music.loop_region.map(|region| region.start),
+                loop_end: music.loop_region.map(|region| region.end),
+                gain_db: (music.gain_db != 0.0).then_some(music.gain_db),
+                ending: music.ending.as_ref().map(|ending| ending.name.clone()),
+                constraints: ConstraintsConfig::default(),
+                shuffle: music.shuffle,
+                next: self.next_configs(&music.next_choices),
+                unknown: UnknownKeys::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let state = self
+            .states
+            .iter()
+            .map(|state| StateConfig {
+                name: state.name.clone(),
+                layers: state
+                    .layers
+                    .iter()
+                    .zip(&self.musics)
+                    .filter_map(|(volume, music)| volume.map(|volume| (music.name.clone(), volume)))
+                    .collect(),
+                constraints: ConstraintsConfig::default(),
+                shuffle: state.shuffle,
+                next: self.next_configs(&state.next_choices),
+                unknown: UnknownKeys::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let config = FeusicConfig {
+            format_version: FORMAT_VERSION,
+            musics: Some(self.musics.iter().map(|m| m.name.clone()).collect()),
+            intro: self.intro.as_ref().map(|intro| intro.name.clone()),
+            ending: self.ending.as_ref().map(|ending| ending.name.clone()),
+            ending_fade: (self.ending_fade != self.fade.duration)
+                .then_some(self.ending_fade.as_millis() as u64),
+            timing: (!has_transitions)
+                .then(|| self.to_timing_string())
+                .transpose()?,
+            start: has_transitions.then(|| StartConfig::new(&self.start)),
+            duration: duration.as_secs(),
+            loop_start,
+            loop_end,
+            fade: (self.fade.duration != DEFAULT_FADE_DURATION)
+                .then_some(self.fade.duration.as_millis() as u64),
+            fade_easing: (self.fade.easing != FadeEasing::default()).then_some(self.fade.easing),
+            bpm: self.tempo.map(|tempo| tempo.bpm),
+            beats_per_bar: self.tempo.map(|tempo| tempo.beats_per_bar),
+            beat_offset: self.tempo.map(|tempo| tempo.beat_offset),
+            quantize: self.tempo.map(|tempo| tempo.quantize),
+            aliases: BTreeMap::new(),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|parameter| {
+                    (
+                        parameter.name.clone(),
+                        ParameterConfig {
+                            min: parameter.min,
+                            max: parameter.max,
+                            default: (parameter.default != parameter.min)
+                                .then_some(parameter.default),
+                            unknown: UnknownKeys::new(),
+                        },
+                    )
+                })
+                .collect(),
+            meta: self.meta.clone(),
+            music,
+            state,
+            unknown: UnknownKeys::new(),
+        };
+
+        Ok(toml::to_string(&config)?)
+    }
+}
+
+impl FeusicConfig {
+    /// Reads a feusic.toml of any supported format version, upgrading it to the latest.
+    pub fn from_toml(
+        feusic_toml: &str,
+        audio_files_names: &[String],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut table: toml::Table = toml::from_str(feusic_toml)
+            .map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+
+        let version = format::format_version(&table)?;
+        format::upgrade(&mut table, audio_files_names)?;
+
+        let config: Self = table
+            .try_into()
+            .map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+        if version >= format::STRICT_FORMAT_VERSION {
+            config.reject_unknown_keys()?;
+        }
+
+        Ok(config)
+    }
+
+    /// Fails when the feusic.toml has keys that are not part of its format, they are most
+    /// likely misspelled.
+    pub fn reject_unknown_keys(&self) -> Result<(), String> {
+        let unknown_keys = self.unknown_keys();
+        if unknown_keys.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Failed to read feusic.toml. Unknown keys: {}",
+            unknown_keys
+                .iter()
+                .map(|key| format!("`{}`", key))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+
+    /// Paths of the unknown keys of the feusic.toml, e.g. `music[0].next[1].cooldwn`.
+    fn unknown_keys(&self) -> Vec<String> {
+        let mut keys = prefixed_keys("", &self.unknown)
+            .chain(prefixed_keys("meta.", &self.meta.unknown))
+            .collect::<Vec<_>>();
+
+        for (name, parameter) in self.parameters.iter() {
+            keys.extend(prefixed_keys(
+                &format!("parameters.{}.", name),
+                &parameter.unknown,
+            ));
+        }
+
+        let nodes =
+            self.music
+                .iter()
+                .enumerate()
+                .map(|(index, music)| (format!("music[{}]", index), &music.unknown, &music.next))
+                .chain(self.state.iter().enumerate().map(|(index, state)| {
+                    (format!("state[{}]", index), &state.unknown, &state.next)
+                }));
+        for (table, unknown, next) in nodes {
+            keys.extend(prefixed_keys(&format!("{}.", table), unknown));
+            for (next_index, next) in next.iter().enumerate() {
+                keys.extend(prefixed_keys(
+                    &format!("{}.next[{}].", table, next_index),
+                    &next.unknown,
+                ));
+            }
+        }
+
+        keys
+    }
+    /// Fade of the transitions that do not define their own.
+    pub fn default_fade(&self) -> Fade {
+        Fade {
+            duration: self
+                .fade
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_FADE_DURATION),
+            easing: self.fade_easing.unwrap_or_default(),
+        }
+    }
+
+    /// Tempo of the feusic, transitions are quantized to bars by default when it is defined.
+    pub fn tempo(&self) -> Result<Option<Tempo>, Box<dyn Error>> {
+        let Some(bpm) = self.bpm else {
+            if self.beats_per_bar.is_some() || self.beat_offset.is_some() || self.quantize.is_some()
+            {
+                return Err(
+                    "`bpm` is required to use beats_per_bar, beat_offset or quantize".into(),
+                );
+            }
+            return Ok(None);
+        };
+
+        // NaN fails every comparison, it is rejected with the infinities.
+        if !(bpm.is_finite() && bpm > 0.0) {
+            return Err(format!("`bpm` should be greater than 0, found {}", bpm).into());
+        }
+        let beat_offset = self.beat_offset.unwrap_or(0.0);
+        if !beat_offset.is_finite() {
+            return Err(format!("`beat_offset` should be a number, found {}", beat_offset).into());
+        }
+
+        let beats_per_bar = self.beats_per_bar.unwrap_or(4);
+        if beats_per_bar == 0 {
+            return Err("`beats_per_bar` should be greater than 0".into());
+        }
+
+        Ok(Some(Tempo {
+            bpm,
+            beats_per_bar,
+            beat_offset,
+            quantize: self.quantize.unwrap_or(Quantize::Bar),
+        }))
+    }
+
+    /// Names of the musics in index order, the `musics` list when defined, otherwise the audio
+    /// files sorted by name.
+    pub fn ordered_musics(
+        &self,
+        audio_files_names: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let clips = self.clips();
+        let Some(musics) = &self.musics else {
+            let mut musics_names = audio_files_names
+                .iter()
+                .filter(|name| !clips.iter().any(|(_, clip)| clip == name))
+                .cloned()
+                .collect::<Vec<_>>();
+            musics_names.sort();
+            return Ok(musics_names);
+        };
+
+        for (music_position, music_name) in musics.iter().enumerate() {
+            if !audio_files_names.contains(music_name) {
+                return Err(format!("`musics`: {} is not in the feusic", music_name).into());
+            }
+            if let Some((clip_kind, _)) = clips.iter().find(|(_, clip)| clip == music_name) {
+                return Err(format!(
+                    "`musics`: {} is {} and cannot be a music",
+                    music_name, clip_kind
+                )
+                .into());
+            }
+            if musics[..music_position].contains(music_name) {
+                return Err(format!("`musics`: {} is listed more than once", music_name).into());
+            }
+        }
+
+        Ok(musics.clone())
+    }
+
+    /// Audio files of the feusic played once instead of looping with the musics, with what
+    /// they are used for.
+    pub fn clips(&self) -> Vec<(&'static str, &str)> {
+        self.intro
+            .iter()
+            .map(|intro| ("the `intro`", intro.as_str()))
+            .chain(
+                self.ending
+                    .iter()
+                    .chain(self.music.iter().flat_map(|music| &music.ending))
+                    .map(|ending| ("an ending", ending.as_str())),
+            )
+            .chain(
+                self.stingers()
+                    .into_iter()
+                    .map(|stinger| ("a stinger", stinger)),
+            )
+            .collect()
+    }
+
+    /// File names of the stingers of the transitions, each one once in order of appearance.
+    pub fn stingers(&self) -> Vec<&str> {
+        let mut stingers: Vec<&str> = vec![];
+        for next in self
+            .music
+            .iter()
+            .flat_map(|music| &music.next)
+            .chain(self.state.iter().flat_map(|state| &state.next))
+        {
+            match &next.stinger {
+                Some(stinger) if !stingers.contains(&stinger.as_str()) => stingers.push(stinger),
+                _ => {}
+            }
+        }
+        stingers
+    }
+
+    /// The `[parameters]` of the feusic, sorted by name. `min` and `max` default to 0 and 1,
+    /// `default` to `min`.
+    pub fn parameters(&self) -> Result<Vec<Parameter>, Box<dyn Error>> {
+        self.parameters
+            .iter()
+            .map(|(name, parameter)| {
+                let default = parameter.default.unwrap_or(parameter.min);
+                if parameter.min > parameter.max {
+                    return Err(format!(
+                        "[parameters] {}: min ({}) is greater than max ({})",
+                        name, parameter.min, parameter.max
+                    ));
+                }
+                if !(parameter.min..=parameter.max).contains(&default) {
+                    return Err(format!(
+                        "[parameters] {}: default ({}) is not between min ({}) and max ({})",
+                        name, default, parameter.min, parameter.max
+                    ));
+                }
+
+                Ok(Parameter {
+                    name: name.clone(),
+                    min: parameter.min,
+                    max: parameter.max,
+                    default,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.into())
+    }
+
+    /// Names that reference the musics, their file stems and names plus the `[aliases]`.
+    pub fn music_names(&self, musics_names: &[String]) -> Result<MusicNames, Box<dyn Error>> {
+        MusicNames::new(musics_names, &self.aliases)
+    }
+
+    /// Returns the musics to start from and the transitions of every music, read either
+    /// from the `timing` string or from the `[[music]]` tables.
+    pub fn timing_musics(
+        &self,
+        names: &MusicNames,
+    ) -> Result<(Vec<Start>, Vec<ParsedTimingMusic>), Box<dyn Error>> {
+        let has_next_tables = self.music.iter().any(|m| !m.next.is_empty());
+
+        match (&self.timing, has_next_tables) {
+            (Some(_), true) => {
+                Err("feusic.toml cannot define both `timing` and [[music.next]] tables".into())
+            }
+            (Some(timing), false) => {
+                let invalid_timing = |error| InvalidTimingError {
+                    timing: timing.clone(),
+                    error,
+                };
+                let parsed_timing =
+                    ParsedTiming::try_from(timing.as_str(), names).map_err(invalid_timing)?;
+                let timing_musics = parsed_timing
+                    .timing_musics
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(invalid_timing)?;
+
+                Ok((parsed_timing.start, timing_musics))
+            }
+            (None, false) => {
+                Err("feusic.toml should define either `timing` or [[music.next]] tables".into())
+            }
+            (None, true) => self.structured_timing_musics(names),
+        }
+    }
+
+    fn structured_timing_musics(
+        &self,
+        names: &MusicNames,
+    ) -> Result<(Vec<Start>, Vec<ParsedTimingMusic>), Box<dyn Error>> {
+        let musics_count = names.musics_count();
+        let start = self
+            .start
+            .as_ref()
+            .ok_or("`start` is required when transitions are defined with [[music]] tables")?
+            .start(
+                |music| names.resolve_ref(music).map_err(|e| e.to_string()),
+                musics_count,
+            )?;
+
+        let mut timing_musics: Vec<ParsedTimingMusic> = vec![];
+
+        for (music_index, music_table) in self.music_tables(names)?.into_iter().enumerate() {
+            let Some(MusicTable {
+                name: music_table,
+                config: music,
+            }) = music_table
+            else {
+                continue;
+            };
+
+            let choices = self.next_choices(
+                &music_table,
+                "[[music.next]]",
+                &music.next,
+                |target| names.resolve_ref(target).map_err(|e| e.to_string()),
+                musics_count,
+            )?;
+
+            timing_musics.push(ParsedTimingMusic {
+                music_index,
+                choices,
+            });
+        }
+
+        Ok((start, timing_musics))
+    }
+
+    /// Returns the states to start from and the states of a layered feusic, `None` when the feusic
+    /// has no `[[state]]` tables. Layers reference musics, transitions reference states.
+    pub fn layer_states(&self, names: &MusicNames) -> Result<Option<LayerStates>, Box<dyn Error>> {
+        if self.state.is_empty() {
+            return Ok(None);
+        }
+
+        if self.timing.is_some() || self.music.iter().any(|m| !m.next.is_empty()) {
+            return Err(
+                "feusic.toml with [[state]] tables defines its transitions in [[state.next]], not in `timing` or [[music.next]]"
+                    .into(),
+            );
+        }
+
+        let resolve_state = |state_ref: &MusicRef| match state_ref {
+            MusicRef::Index(state_index) => Ok(*state_index),
+            MusicRef::Name(name) => self
+                .state
+                .iter()
+                .position(|state| state.name == *name)
+                .ok_or_else(|| format!("no state named @{}", name)),
+        };
+
+        let start = self
+            .start
+            .as_ref()
+            .ok_or("`start` is required when defining [[state]] tables")?
+            .start(resolve_state, self.state.len())?;
+
+        let states_constraints = self
+            .state
+            .iter()
+            .enumerate()
+            .map(|(state_position, state)| {
+                state.constraints.constraints().map_err(|e| {
+                    format!("[[state]] #{} ({}): {}", state_position + 1, state.name, e)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let states = self
+            .state
+            .iter()
+            .enumerate()
+            .map(|(state_position, state)| {
+                let state_table = format!("[[state]] #{} ({})", state_position + 1, state.name);
+
+                if self.state[..state_position]
+                    .iter()
+                    .any(|other| other.name == state.name)
+                {
+                    return Err(format!("{}: name is already used", state_table).into());
+                }
+
+                let mut layers = vec![None; names.musics_count()];
+                for (layer, volume) in state.layers.iter() {
+                    let music_index = match layer.parse::<usize>() {
+                        Ok(music_index) => music_index,
+                        Err(_) => names
+                            .resolve(layer)
+                            .map_err(|e| format!("{}: layers: {}", state_table, e))?,
+                    };
+                    *layers.get_mut(music_index).ok_or_else(|| {
+                        format!("{}: layers: no music at index {}", state_table, music_index)
+                    })? = Some(*volume);
+                }
+
+                Ok(LayerState {
+                    name: state.name.clone(),
+                    layers,
+                    next_choices: self
+                        .next_choices(
+                            &state_table,
+                            "[[state.next]]",
+                            &state.next,
+                            resolve_state,
+                            self.state.len(),
+                        )?
+                        .into_iter()
+                        .map(|choice| choice.into_next(self.default_fade(), &states_constraints))
+                        .collect(),
+                    shuffle: state.shuffle,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+        Ok(Some(LayerStates { start, states }))
+    }
+
+    /// Parses the `next_kind` tables, `[[music.next]]` or `[[state.next]]`, of the `table`.
+    /// `resolve_target` gives the index of a target that has to be lower than `targets_count`.
+    fn next_choices(
+        &self,
+        table: &str,
+        next_kind: &str,
+        next: &[NextConfig],
+        resolve_target: impl Fn(&MusicRef) -> Result<usize, String>,
+        targets_count: usize,
+    ) -> Result<Vec<ParsedTimingChoice>, Box<dyn Error>> {
+        let stingers = self.stingers();
+        let parameters = self.parameters()?;
+
+        if next.len() > 1 && next.iter().any(|n| n.weight.unwrap_or(0) == 0) {
+            return Err(format!(
+                "{}: when defining multiple {} they all need a weight different than 0",
+                table, next_kind
+            )
+            .into());
+        }
+
+        Ok(next
+            .iter()
+            .enumerate()
+            .map(|(next_position, next)| {
+                let next_table = format!("{} {} #{}", table, next_kind, next_position + 1);
+                let wait_min = next
+                    .wait_min
+                    .wait()
+                    .map_err(|e| format!("{}: wait_min: {}", next_table, e))?;
+                let wait_max = match &next.wait_max {
+                    Some(wait_max) => wait_max
+                        .wait()
+                        .map_err(|e| format!("{}: wait_max: {}", next_table, e))?,
+                    None => wait_min,
+                };
+                let target =
+                    resolve_target(&next.target).map_err(|e| format!("{}: {}", next_table, e))?;
+
+                if next.weight == Some(0) {
+                    return Err(format!("{}: weight should be greater than 0", next_table));
+                }
+
+                if target >= targets_count {
+                    return Err(format!(
+                        "{}: target index {} does not exist",
+                        next_table, target
+                    ));
+                }
+
+                if wait_min.is_longer_than(&wait_max) {
+                    return Err(format!(
+                        "{}: wait_min ({}) is greater than wait_max ({})",
+                        next_table, wait_min, wait_max
+                    ));
+                }
+
+                let stinger = match (&next.stinger, next.duck_db) {
+                    (Some(stinger), duck_db) => Some(Stinger {
+                        clip: stingers
+                            .iter()
+                            .position(|name| name == stinger)
+                            .expect("stingers are collected from the next tables"),
+                        duck_db: duck_db.unwrap_or(0.0),
+                    }),
+                    (None, Some(_)) => {
+                        return Err(format!("{}: duck_db needs a stinger", next_table));
+                    }
+                    (None, None) => None,
+                };
+
+                let when = next
+                    .when
+                    .as_ref()
+                    .map(|when| Condition::parse(when, &parameters))
+                    .transpose()
+                    .map_err(|e| format!("{}: when: {}", next_table, e))?;
+
+                let constraints = next
+                    .constraints
+                    .constraints()
+                    .map_err(|e| format!("{}: {}", next_table, e))?;
+
+                Ok(ParsedTimingChoice {
+                    probability_weight: next.weight.unwrap_or(100),
+                    target_music_index: target,
+                    wait: (wait_min, wait_max),
+                    fade: next.fade.map(|fade| fade as usize),
+                    easing: next.easing,
+                    stinger,
+                    when,
+                    constraints,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Returns the `[[music]]` table of every music, if it has one.
+    pub fn music_tables(
+        &self,
+        names: &MusicNames,
+    ) -> Result<Vec<Option<MusicTable<'_>>>, Box<dyn Error>> {
+        let mut music_tables = (0..names.musics_count()).map(|_| None).collect::<Vec<_>>();
+
+        for (music_position, music) in self.music.iter().enumerate() {
+            let name = format!("[[music]] #{} ({})", music_position + 1, music.index);
+            let music_index = names
+                .resolve_ref(&music.index)
+                .map_err(|e| format!("{}: {}", name, e))?;
+
+            let music_table = music_tables
+                .get_mut(music_index)
+                .ok_or_else(|| format!("{}: no music at index {}", name, music_index))?;
+
+            if music_table.is_some() {
+                return Err(format!("{}: music {} is already defined", name, music_index).into());
+            }
+
+            *music_table = Some(MusicTable {
+                name,
+                config: music,
+            });
+        }
+
+        Ok(music_tables)
+    }
+}
+
+impl<M> Feusic<M> {
+    /// The `next` tables of the transitions of a music or state, their fade is only written
+    /// when it is not the one of the feusic.
+    fn next_configs(&self, next_choices: &[Next]) -> Vec<NextConfig> {
+        next_choices
+            .iter()
+            .map(|next| NextConfig {
+                target: MusicRef::Index(next.target_music),
+                wait_min: WaitConfig::new(next.wait.0),
+                wait_max: (next.wait.1 != next.wait.0).then(|| WaitConfig::new(next.wait.1)),
+                weight: (next_choices.len() > 1 || next.probability_weight != 100)
+                    .then_some(next.probability_weight),
+                fade: (next.fade.duration != self.fade.duration)
+                    .then_some(next.fade.duration.as_millis() as u64),
+                easing: (next.fade.easing != self.fade.easing).then_some(next.fade.easing),
+                stinger: next
+                    .stinger
+                    .and_then(|stinger| self.stingers.get(stinger.clip))
+                    .map(|clip| clip.name.clone()),
+                duck_db: next
+                    .stinger
+                    .and_then(|stinger| (stinger.duck_db != 0.0).then_some(stinger.duck_db)),
+                when: next
+                    .when
+                    .as_ref()
+                    .map(|when| when.describe(&self.parameters)),
+                constraints: ConstraintsConfig {
+                    cooldown: next
+                        .constraints
+                        .cooldown
+                        .map(|cooldown| cooldown.as_millis() as u64),
+                    cooldown_transitions: next.constraints.cooldown_transitions,
+                    max_consecutive: next.constraints.max_consecutive,
+                },
+                unknown: UnknownKeys::new(),
+            })
+            .collect()
+    }
+}
+
+impl MusicConfig {
+    pub fn new(index: MusicRef) -> Self {
+        Self {
+            index,
+            title: None,
+            loop_start: None,
+            loop_end: None,
+            gain_db: None,
+            ending: None,
+            constraints: ConstraintsConfig::default(),
+            shuffle: false,
+            next: vec![],
+            unknown: UnknownKeys::new(),
+        }
+    }
+}
+
+impl StartConfig {
+    pub fn new(start: &[Start]) -> Self {
+        match start {
+            [Start {
+                node,
+                probability_weight: 100,
+            }] => StartConfig::Single(MusicRef::Index(*node)),
+            _ => StartConfig::Weighted(
+                start
+                    .iter()
+                    .map(|start| (start.node.to_string(), start.probability_weight))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Resolves the musics or states to start from, `resolve` gives the index of one of them
+    /// that has to be lower than `nodes_count`. Table keys are indices or names.
+    pub fn start(
+        &self,
+        resolve: impl Fn(&MusicRef) -> Result<usize, String>,
+        nodes_count: usize,
+    ) -> Result<Vec<Start>, Box<dyn Error>> {
+        let weighted = match self {
+            StartConfig::Single(node) => vec![(node.clone(), 100)],
+            StartConfig::Weighted(weights) => weights
+                .iter()
+                .map(|(node, weight)| match node.parse::<usize>() {
+                    Ok(index) => (MusicRef::Index(index), *weight),
+                    Err(_) => (MusicRef::Name(node.clone()), *weight),
+                })
+                .collect(),
+        };
+
+        if weighted.is_empty() {
+            return Err("`start` needs at least one music to start from".into());
+        }
+
+        weighted
+            .into_iter()
+            .map(|(node, probability_weight)| {
+                let node = resolve(&node).map_err(|e| format!("`start`: {}", e))?;
+                if node >= nodes_count {
+                    return Err(format!("`start` {} does not exist", node).into());
+                }
+                if probability_weight == 0 {
+                    return Err(format!("`start` {} needs a weight different than 0", node).into());
+                }
+
+                Ok(Start {
+                    node,
+                    probability_weight,
+                })
+            })
+            .collect()
+    }
+}
+
+impl WaitConfig {
+    pub fn new(wait: Wait) -> Self {
+        match wait.unit {
+            WaitUnit::Milliseconds => WaitConfig::Millis(wait.amount),
+            _ => WaitConfig::WithUnit(wait.to_string()),
+        }
+    }
+
+    pub fn wait(&self) -> Result<Wait, String> {
+        match self {
+            WaitConfig::Millis(millis) => Ok(Wait::millis(*millis)),
+            WaitConfig::WithUnit(wait) => Wait::parse(wait),
+        }
+    }
+}
+
+impl ConstraintsConfig {
+    pub fn constraints(&self) -> Result<Constraints, String> {
+        if self.max_consecutive == Some(0) {
+            return Err("max_consecutive should be greater than 0".to_string());
+        }
+
+        Ok(Constraints {
+            cooldown: self.cooldown.map(Duration::from_millis),
+            cooldown_transitions: self.cooldown_transitions,
+            max_consecutive: self.max_consecutive,
+        })
+    }
+}
+
+fn default_parameter_max() -> f64 {
+    1.0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_default_meta(meta: &FeusicMeta) -> bool {
+    *meta == FeusicMeta::default()
+}
+
+impl<'a> MusicTable<'a> {
+    /// Limits on the transitions to the music that do not define their own.
+    pub fn constraints(&self) -> Result<Constraints, Box<dyn Error>> {
+        Ok(self
+            .config
+            .constraints
+            .constraints()
+            .map_err(|e| format!("{}: {}", self.name, e))?)
+    }
+
+    pub fn loop_region(&self) -> Result<Option<LoopRegion>, Box<dyn Error>> {
+        match (self.config.loop_start, self.config.loop_end) {
+            (Some(start), Some(end)) => Ok(Some(LoopRegion { start, end })),
+            (None, None) => Ok(None),
+            _ => Err(format!(
+                "{}: loop_start and loop_end need to be defined together",
+                self.name
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::{
+        loader::FeusicMusicLoader,
+        test_utils::{load, reload, NUMBERED_MUSICS},
+        Clip,
+    };
+
+    use super::*;
+
+    #[test]
+    fn structured_timing_matches_timing_string() {
+        let from_string = load(
+            &NUMBERED_MUSICS[..3],
+            r#"
+                timing = "s1|0:w5000:1|1:w10000-20000;p30:0/w1000-2000;p70:2|2:w3000-4000:1"
+                duration = 600
+            "#,
+        )
+        .unwrap();
+
+        let from_tables = load(
+            &NUMBERED_MUSICS[..3],
+            r#"
+                start = 1
+                duration = 600
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+
+                [[music]]
+                index = 1
+                [[music.next]]
+                target = 0
+                wait_min = 10000
+                wait_max = 20000
+                weight = 30
+                [[music.next]]
+                target = 2
+                wait_min = 1000
+                wait_max = 2000
+                weight = 70
+
+                [[music]]
+                index = 2
+                [[music.next]]
+                target = 1
+                wait_min = 3000
+                wait_max = 4000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(from_tables.start, from_string.start);
+        assert_eq!(from_tables.musics.len(), from_string.musics.len());
+        for (table_music, string_music) in from_tables.musics.iter().zip(&from_string.musics) {
+            assert_eq!(table_music.name, string_music.name);
+            assert_eq!(table_music.next_choices, string_music.next_choices);
+        }
+    }
+
+    #[test]
+    fn structured_timing_errors_name_the_table() {
+        let error = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                start = 0
+                duration = 600
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+
+                [[music]]
+                index = 1
+                [[music.next]]
+                target = 0
+                wait_min = 2000
+                wait_max = 1000
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #2 (index 1) [[music.next]] #1: wait_min (2000) is greater than wait_max (1000)"
+        );
+    }
+
+    #[test]
+    fn zero_weights_fail_to_load() {
+        let error = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                start = 0
+                duration = 600
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+                weight = 0
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (index 0) [[music.next]] #1: weight should be greater than 0"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_fail_to_load() {
+        let error = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                format_version = 2
+                start = 0
+                duration = 600
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+                cooldwn = 1000
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Failed to read feusic.toml. Unknown keys: `music[0].next[0].cooldwn`"
+        );
+    }
+
+    #[test]
+    fn tempos_that_are_not_finite_fail_to_load() {
+        for (tempo, error) in [
+            ("bpm = nan", "`bpm` should be greater than 0, found NaN"),
+            ("bpm = inf", "`bpm` should be greater than 0, found inf"),
+            ("bpm = 0.0", "`bpm` should be greater than 0, found 0"),
+            (
+                "bpm = 120.0\nbeat_offset = -inf",
+                "`beat_offset` should be a number, found -inf",
+            ),
+        ] {
+            let feusic_toml = format!(
+                "timing = \"s0|0:w1000:1|1:w1000:0\"\nduration = 600\n{}",
+                tempo
+            );
+
+            let result = load(&NUMBERED_MUSICS[..2], &feusic_toml);
+
+            assert_eq!(result.unwrap_err().to_string(), error);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_of_older_versions_are_ignored() {
+        let feusic = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                format_version = 1
+                musics = ["0.mp3", "1.mp3"]
+                start = 0
+                duration = 600
+                volume = 0.8
+
+                [meta]
+                album = "OST"
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+                cooldwn = 1000
+
+                [[music]]
+                index = 1
+                [[music.next]]
+                target = 0
+                wait_min = 5000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(feusic.musics[0].next_choices[0].target_music, 1);
+    }
+
+    #[test]
+    fn transitions_fade_with_their_own_or_the_default_fade() {
+        let feusic = load(
+            &NUMBERED_MUSICS[..3],
+            r#"
+                start = 0
+                duration = 600
+                fade = 8000
+                fade_easing = "linear"
+
+                [[music]]
+                index = 0
+                [[music.next]]
+                target = 1
+                wait_min = 5000
+                fade = 200
+                easing = { power = 0.5 }
+
+                [[music]]
+                index = 1
+                [[music.next]]
+                target = 2
+                wait_min = 5000
+
+                [[music]]
+                index = 2
+                [[music.next]]
+                target = 0
+                wait_min = 5000
+                fade = 3000
+            "#,
+        )
+        .unwrap();
+
+        let fades = feusic
+            .musics
+            .iter()
+            .map(|m| m.next_choices[0].fade)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fades,
+            vec![
+                Fade {
+                    duration: Duration::from_millis(200),
+                    easing: FadeEasing::Power(0.5)
+                },
+                Fade {
+                    duration: Duration::from_millis(8000),
+                    easing: FadeEasing::Linear
+                },
+                Fade {
+                    duration: Duration::from_millis(3000),
+                    easing: FadeEasing::Linear
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn layered_feusics_transition_between_states() {
+        let files_names = ["base.mp3", "drums.mp3", "strings.mp3"];
+        let feusic_toml = r#"
+            start = "explore"
+            duration = 600
+
+            [[state]]
+            name = "explore"
+            layers = { base = 0.0, 2 = -6.0 }
+
+            [[state.next]]
+            target = "combat"
+            wait_min = 1000
+            fade = 500
+
+            [[state]]
+            name = "combat"
+            layers = { base = 0.0, drums = -3.0 }
+
+            [[state.next]]
+            target = 0
+            wait_min = 2000
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        assert!(feusic.is_layered());
+        assert_eq!(
+            feusic.start,
+            vec![Start {
+                node: 0,
+                probability_weight: 100
+            }]
+        );
+        assert_eq!(feusic.states[0].layers, vec![Some(0.0), None, Some(-6.0)]);
+        assert_eq!(feusic.states[1].layers, vec![Some(0.0), Some(-3.0), None]);
+        assert_eq!(feusic.states[0].next_choices[0].target_music, 1);
+        assert_eq!(
+            feusic.states[0].next_choices[0].fade.duration,
+            Duration::from_millis(500)
+        );
+        assert_eq!(feusic.music_volume(1, 1), Some(-3.0));
+        assert_eq!(feusic.music_volume(1, 2), None);
+        assert_eq!(feusic.node_titles(), vec!["explore", "combat"]);
+
+        assert_eq!(reload(&feusic, &files_names).states, feusic.states);
+
+        let error = load(
+            &files_names,
+            &feusic_toml.replace("drums = -3.0", "bass = -3.0"),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[state]] #2 (combat): layers: no music named @bass"
+        );
+    }
+
+    #[test]
+    fn intro_is_not_one_of_the_musics() {
+        let files_names = ["feusic.toml", "b.mp3", "intro.mp3", "a.mp3"];
+
+        let feusic = load(
+            &files_names,
+            r#"
+                intro = "intro.mp3"
+                timing = "s1|0:w1000:1|1:w1000:0"
+                duration = 600
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            feusic
+                .musics
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a.mp3", "b.mp3"]
+        );
+        assert!(matches!(
+            &feusic.intro,
+            Some(Clip {
+                loader: FeusicMusicLoader::FolderFeusic { music_path },
+                ..
+            }) if music_path == "intro.mp3"
+        ));
+
+        assert_eq!(
+            reload(&feusic, &files_names).intro.map(|intro| intro.name),
+            Some("intro.mp3".to_string())
+        );
+
+        let error = load(
+            &files_names,
+            r#"
+                format_version = 1
+                musics = ["a.mp3", "intro.mp3"]
+                intro = "intro.mp3"
+                timing = "s1|0:w1000:1|1:w1000:0"
+                duration = 600
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`musics`: intro.mp3 is the `intro` and cannot be a music"
+        );
+    }
+
+    #[test]
+    fn transitions_can_play_stingers() {
+        let files_names = ["map.mp3", "battle.mp3", "cymbal.wav"];
+        let feusic_toml = r#"
+            start = "map"
+            duration = 600
+
+            [[music]]
+            index = "map"
+
+            [[music.next]]
+            target = "battle"
+            wait_min = 1000
+            stinger = "cymbal.wav"
+            duck_db = -6.0
+
+            [[music]]
+            index = "battle"
+
+            [[music.next]]
+            target = "map"
+            wait_min = 1000
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        assert_eq!(
+            feusic
+                .musics
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["battle.mp3", "map.mp3"]
+        );
+        assert_eq!(
+            feusic
+                .stingers
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["cymbal.wav"]
+        );
+        assert_eq!(
+            feusic.musics[1].next_choices[0].stinger,
+            Some(Stinger {
+                clip: 0,
+                duck_db: -6.0
+            })
+        );
+        assert_eq!(feusic.musics[0].next_choices[0].stinger, None);
+
+        assert_eq!(
+            reload(&feusic, &files_names).musics[1].next_choices,
+            feusic.musics[1].next_choices
+        );
+
+        let error = load(
+            &files_names,
+            &feusic_toml.replace("stinger = \"cymbal.wav\"", ""),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (@map) [[music.next]] #1: duck_db needs a stinger"
+        );
+    }
+
+    #[test]
+    fn stingers_of_layered_transitions_duck_only_the_layers_fading_out() {
+        let files_names = ["base.mp3", "drums.mp3", "strings.mp3", "cymbal.wav"];
+
+        let feusic = load(
+            &files_names,
+            r#"
+                start = "explore"
+                duration = 600
+                musics = ["base.mp3", "drums.mp3", "strings.mp3"]
+
+                [[state]]
+                name = "explore"
+                layers = { base = 0.0, drums = 0.0 }
+
+                [[state.next]]
+                target = "combat"
+                wait_min = 1000
+                stinger = "cymbal.wav"
+                duck_db = -12.0
+
+                [[state]]
+                name = "combat"
+                layers = { base = -6.0, strings = 0.0 }
+
+                [[state.next]]
+                target = "explore"
+                wait_min = 1000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            feusic.states[0].next_choices[0].stinger,
+            Some(Stinger {
+                clip: 0,
+                duck_db: -12.0
+            })
+        );
+        assert_eq!(
+            (0..3)
+                .map(|music_index| feusic.is_ducked_by_stinger(0, 1, music_index))
+                .collect::<Vec<_>>(),
+            vec![false, true, false]
+        );
+    }
+
+    #[test]
+    fn endings_are_read_per_feusic_and_per_music() {
+        let files_names = ["map.mp3", "battle.mp3", "ending.mp3", "victory.mp3"];
+
+        let feusic = load(
+            &files_names,
+            r#"
+                timing = "s@map|@map:w1000:@battle|@battle:w1000:@map"
+                duration = 600
+                ending = "ending.mp3"
+                ending_fade = 0
+
+                [[music]]
+                index = "battle"
+                ending = "victory.mp3"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            feusic
+                .musics
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["battle.mp3", "map.mp3"]
+        );
+        assert_eq!(feusic.ending_fade, Duration::ZERO);
+        assert_eq!(
+            feusic.ending_of(0).map(|ending| ending.name.as_str()),
+            Some("victory.mp3")
+        );
+        assert_eq!(
+            feusic.ending_of(1).map(|ending| ending.name.as_str()),
+            Some("ending.mp3")
+        );
+
+        let from_feusic_toml = reload(&feusic, &files_names);
+
+        assert_eq!(from_feusic_toml.ending_fade, Duration::ZERO);
+        assert_eq!(
+            from_feusic_toml
+                .ending_of(0)
+                .map(|ending| ending.name.as_str()),
+            Some("victory.mp3")
+        );
+    }
+
+    #[test]
+    fn edited_transitions_are_saved() {
+        let files_names = ["map.mp3", "town.mp3"];
+        let mut feusic = load(
+            &files_names,
+            "timing = \"s0|0:w5000:1|1:w5000:0\"\nduration = 600",
+        )
+        .unwrap();
+
+        let mut transitions = feusic.transitions();
+        transitions[0][0].wait.1 = Wait::millis(8000);
+        let to_itself = Next {
+            target_music: 1,
+            ..transitions[1][0].clone()
+        };
+        transitions[1].push(to_itself);
+        feusic.set_transitions(transitions.clone());
+        feusic.start = vec![Start {
+            node: 1,
+            probability_weight: 100,
+        }];
+
+        let saved = reload(&feusic, &files_names);
+
+        assert_eq!(saved.transitions(), transitions);
+        assert_eq!(saved.start, feusic.start);
+    }
+}
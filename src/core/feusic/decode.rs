@@ -0,0 +1,73 @@
+use std::{error::Error, io::Cursor, io::Read, path::Path, time::Duration};
+
+use symphonia::core::{
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use super::loader::MusicLoader;
+
+/// Duration of the music, from its header or else by going through all its packets.
+pub fn measure_duration<M: MusicLoader>(
+    loader: &M,
+    music_name: &str,
+) -> Result<Duration, Box<dyn Error>> {
+    let mut format = open_format(loader, music_name)?;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| format!("{} has no audio track", music_name))?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+
+    if let (Some(n_frames), Some(sample_rate)) = (codec_params.n_frames, codec_params.sample_rate) {
+        return Ok(Duration::from_secs_f64(
+            n_frames as f64 / sample_rate as f64,
+        ));
+    }
+
+    let time_base = codec_params
+        .time_base
+        .ok_or_else(|| format!("{} has no time base", music_name))?;
+    let mut timestamps = 0;
+    loop {
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => timestamps += packet.dur(),
+            Ok(_) => {}
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let time = time_base.calc_time(timestamps);
+    Ok(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+}
+
+/// Reads the whole music and opens its container to go through its packets.
+pub fn open_format<M: MusicLoader>(
+    loader: &M,
+    music_name: &str,
+) -> Result<Box<dyn FormatReader>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    loader.read()?.reader.read_to_end(&mut bytes)?;
+
+    let mut hint = Hint::new();
+    if let Some(extension) = Path::new(music_name).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let media_source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    Ok(symphonia::default::get_probe()
+        .format(
+            &hint,
+            media_source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?
+        .format)
+}
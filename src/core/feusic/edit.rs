@@ -0,0 +1,488 @@
+use std::{collections::BTreeMap, error::Error, str::FromStr};
+
+use toml_edit::{value, ArrayOfTables, DocumentMut, Item, Table, Value};
+
+use super::{
+    names::{MusicNames, MusicRef},
+    timing::{InvalidTimingError, ParsedTiming, Wait, WaitUnit},
+    Feusic, Next, Start,
+};
+
+impl<M> Feusic<M> {
+    /// Writes the start and the transitions of the feusic into `feusic_toml`, the feusic.toml
+    /// it was loaded from when they were `saved_start` and `saved_transitions`. Only what
+    /// changed is rewritten, the `start` and the `next` of the edited musics or states, or
+    /// their part of the `timing` string. The rest of the file stays as it is, comments
+    /// included, and an edited transition keeps the keys of its table that did not change,
+    /// e.g. its stinger or a target referenced by name.
+    pub fn edit_feusic_toml(
+        &self,
+        feusic_toml: &str,
+        saved_start: &[Start],
+        saved_transitions: &[Vec<Next>],
+    ) -> Result<String, Box<dyn Error>> {
+        let mut document = DocumentMut::from_str(feusic_toml)
+            .map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+        let names = self.document_names(&document)?;
+
+        let transitions = self.transitions();
+        let edited_nodes = (0..transitions.len())
+            .filter(|node| saved_transitions.get(*node) != Some(&transitions[*node]))
+            .collect::<Vec<_>>();
+        let start_edited = self.start != saved_start;
+
+        if document.contains_key("timing") {
+            self.edit_timing(&mut document, &names, start_edited, &edited_nodes)?;
+        } else {
+            if transitions
+                .iter()
+                .all(|next_choices| next_choices.is_empty())
+            {
+                return Err("the feusic needs at least one transition".into());
+            }
+            self.edit_tables(
+                &mut document,
+                &names,
+                start_edited,
+                &edited_nodes,
+                saved_transitions,
+            )?;
+        }
+
+        Ok(document.to_string())
+    }
+
+    /// Names of the musics with the `[aliases]` of the document.
+    fn document_names(&self, document: &DocumentMut) -> Result<MusicNames, Box<dyn Error>> {
+        let aliases = document
+            .get("aliases")
+            .and_then(Item::as_table_like)
+            .into_iter()
+            .flat_map(|aliases| aliases.iter())
+            .filter_map(|(alias, music)| Some((alias.to_string(), music.as_str()?.to_string())))
+            .collect::<BTreeMap<_, _>>();
+        let musics_names = self
+            .musics
+            .iter()
+            .map(|music| music.name.clone())
+            .collect::<Vec<_>>();
+
+        MusicNames::new(&musics_names, &aliases)
+    }
+
+    /// Replaces the start and the musics of the `timing` string that were edited, the other
+    /// ones keep their text.
+    fn edit_timing(
+        &self,
+        document: &mut DocumentMut,
+        names: &MusicNames,
+        start_edited: bool,
+        edited_nodes: &[usize],
+    ) -> Result<(), Box<dyn Error>> {
+        let timing = document["timing"]
+            .as_str()
+            .ok_or("`timing` should be a string")?
+            .to_string();
+        let invalid_timing = |error| InvalidTimingError {
+            timing: timing.clone(),
+            error,
+        };
+        let music_indices = ParsedTiming::try_from(timing.as_str(), names)
+            .map_err(invalid_timing)?
+            .timing_musics
+            .map(|timing_music| timing_music.map(|timing_music| timing_music.music_index))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid_timing)?;
+
+        let timing_music = |music_index: usize| {
+            let next_choices = &self.musics[music_index].next_choices;
+            (!next_choices.is_empty())
+                .then(|| format!("{}:{}", music_index, self.timing_choices(next_choices)))
+        };
+
+        // Musics are separated by `|`, which cannot be in their names.
+        let mut parts = timing.split('|');
+        let start = parts.next().unwrap_or_default();
+        let mut edited = vec![match start_edited {
+            true => self.timing_start(),
+            false => start.to_string(),
+        }];
+        for (part, music_index) in parts.zip(&music_indices) {
+            match edited_nodes.contains(music_index) {
+                true => edited.extend(timing_music(*music_index)),
+                false => edited.push(part.to_string()),
+            }
+        }
+        for music_index in edited_nodes {
+            if !music_indices.contains(music_index) {
+                edited.extend(timing_music(*music_index));
+            }
+        }
+
+        set_value(&mut document["timing"], edited.join("|"));
+
+        Ok(())
+    }
+
+    /// Replaces the `start` and the `next` of the `[[music]]` or `[[state]]` tables that were
+    /// edited. Transitions are matched with the table they were loaded from by what the editor
+    /// does not change, the other ones come from [`Feusic::to_feusic_toml`].
+    fn edit_tables(
+        &self,
+        document: &mut DocumentMut,
+        names: &MusicNames,
+        start_edited: bool,
+        edited_nodes: &[usize],
+        saved_transitions: &[Vec<Next>],
+    ) -> Result<(), Box<dyn Error>> {
+        let serialized = DocumentMut::from_str(&self.to_feusic_toml()?)?;
+
+        if start_edited {
+            let start = serialized["start"]
+                .as_value()
+                .ok_or("the feusic needs a start")?;
+            set_value(&mut document["start"], start.clone());
+        }
+
+        let transitions = self.transitions();
+        for node in edited_nodes.iter().copied() {
+            let serialized_tables = match self.is_layered() {
+                true => serialized["state"]
+                    .as_array_of_tables()
+                    .and_then(|states| states.get(node)),
+                false => serialized["music"]
+                    .as_array_of_tables()
+                    .into_iter()
+                    .flat_map(|musics| musics.iter())
+                    .find(|music| music["index"].as_integer() == Some(node as i64)),
+            }
+            .map(|table| next_tables(&table["next"]).0)
+            .unwrap_or_default();
+
+            let table = self.node_table(document, names, node)?;
+            let (saved_tables, inline) = next_tables(&table["next"]);
+            let saved = saved_transitions.get(node).map(Vec::as_slice);
+            // Tables of the document that do not match what was loaded are all replaced.
+            let saved = match saved {
+                Some(saved) if saved.len() == saved_tables.len() => saved,
+                _ => &[],
+            };
+
+            let next_choices = &transitions[node];
+            let mut used = vec![false; saved.len()];
+            let mut tables = vec![];
+            for (position, next) in next_choices.iter().enumerate() {
+                let matching = std::iter::once(position)
+                    .chain(0..saved.len())
+                    .find(|j| *j < saved.len() && !used[*j] && same_unedited(&saved[*j], next));
+
+                match matching {
+                    Some(j) => {
+                        used[j] = true;
+                        let mut edited = saved_tables[j].clone();
+                        edit_next_table(&mut edited, &saved[j], next, next_choices.len());
+                        tables.push(edited);
+                    }
+                    None => tables.push(unpositioned(
+                        serialized_tables
+                            .get(position)
+                            .ok_or("the serialized feusic misses a transition")?,
+                    )),
+                }
+            }
+
+            set_next_tables(table, tables, inline);
+        }
+
+        Ok(())
+    }
+
+    /// The `[[state]]` table of a state, or the `[[music]]` table of a music, added when the
+    /// music has none.
+    fn node_table<'a>(
+        &self,
+        document: &'a mut DocumentMut,
+        names: &MusicNames,
+        node: usize,
+    ) -> Result<&'a mut Table, Box<dyn Error>> {
+        if self.is_layered() {
+            return document["state"]
+                .as_array_of_tables_mut()
+                .and_then(|states| states.get_mut(node))
+                .ok_or_else(|| format!("feusic.toml has no [[state]] #{}", node + 1).into());
+        }
+
+        music_table(document, names, node)
+    }
+}
+
+/// The `[[music]]` table of a music, added when the music has none.
+pub(super) fn music_table<'a>(
+    document: &'a mut DocumentMut,
+    names: &MusicNames,
+    music_index: usize,
+) -> Result<&'a mut Table, Box<dyn Error>> {
+    if !document.contains_key("music") {
+        document.insert("music", Item::ArrayOfTables(ArrayOfTables::new()));
+    }
+    let musics = document["music"]
+        .as_array_of_tables_mut()
+        .ok_or("`music` should be [[music]] tables")?;
+
+    let mut position = None;
+    for (music_position, music) in musics.iter().enumerate() {
+        let music_ref = match music.get("index").and_then(Item::as_value) {
+            Some(Value::Integer(index)) => MusicRef::Index(*index.value() as usize),
+            Some(Value::String(name)) => MusicRef::Name(name.value().clone()),
+            _ => continue,
+        };
+        if names.resolve_ref(&music_ref) == Ok(music_index) {
+            position = Some(music_position);
+            break;
+        }
+    }
+
+    let position = match position {
+        Some(position) => position,
+        None => {
+            let mut music = Table::new();
+            music.insert("index", value(music_index as i64));
+            musics.push(music);
+            musics.len() - 1
+        }
+    };
+
+    Ok(musics.get_mut(position).expect("the music table exists"))
+}
+
+/// Whether two transitions only differ by what the editor changes, their target, waits and
+/// weight.
+fn same_unedited(saved: &Next, next: &Next) -> bool {
+    saved.fade == next.fade
+        && saved.stinger == next.stinger
+        && saved.when == next.when
+        && saved.constraints == next.constraints
+}
+
+/// Sets the keys of a `next` table that changed from `saved` to `next`. All the transitions
+/// of a music need a weight when it has more than one.
+fn edit_next_table(table: &mut Table, saved: &Next, next: &Next, next_count: usize) {
+    if next.target_music != saved.target_music {
+        table.insert("target", value(next.target_music as i64));
+    }
+
+    if next.wait != saved.wait {
+        table.insert("wait_min", wait_item(next.wait.0));
+        if next.wait.1 == next.wait.0 {
+            table.remove("wait_max");
+        } else {
+            table.insert("wait_max", wait_item(next.wait.1));
+        }
+    }
+
+    if next.probability_weight != saved.probability_weight
+        || (next_count > 1 && !table.contains_key("weight"))
+    {
+        table.insert("weight", value(next.probability_weight as i64));
+    }
+}
+
+/// A wait in milliseconds, or a string with its unit.
+fn wait_item(wait: Wait) -> Item {
+    match wait.unit {
+        WaitUnit::Milliseconds => value(wait.amount as i64),
+        _ => value(wait.to_string()),
+    }
+}
+
+/// The tables of a `next` array, written as `[[music.next]]` tables or as an inline array,
+/// and whether it is inline.
+fn next_tables(next: &Item) -> (Vec<Table>, bool) {
+    match next {
+        Item::ArrayOfTables(tables) => (tables.iter().cloned().collect(), false),
+        Item::Value(Value::Array(array)) => (
+            array
+                .iter()
+                .filter_map(Value::as_inline_table)
+                .map(|table| table.clone().into_table())
+                .collect(),
+            true,
+        ),
+        _ => (vec![], false),
+    }
+}
+
+/// Replaces the `next` array of a table, in the same form it had.
+fn set_next_tables(table: &mut Table, tables: Vec<Table>, inline: bool) {
+    if tables.is_empty() {
+        table.remove("next");
+        return;
+    }
+
+    if inline {
+        let array = tables
+            .into_iter()
+            .map(|table| Value::InlineTable(table.into_inline_table()))
+            .collect();
+        set_value(&mut table["next"], Value::Array(array));
+        return;
+    }
+
+    // Tables are written in the order of their position in the document, the ones without
+    // follow the table before them.
+    let mut next = ArrayOfTables::new();
+    for mut next_table in tables {
+        match table.position() {
+            Some(position) => next_table.set_position(position),
+            None => next_table = unpositioned(&next_table),
+        }
+        next.push(next_table);
+    }
+    table.insert("next", Item::ArrayOfTables(next));
+}
+
+/// Copy of a table without its position in the document it comes from.
+fn unpositioned(table: &Table) -> Table {
+    let mut copy = Table::new();
+    for (key, item) in table.iter() {
+        copy.insert(key, item.clone());
+    }
+    copy
+}
+
+/// Sets a value keeping the comments and spaces around the one it replaces.
+pub(super) fn set_value(item: &mut Item, new: impl Into<Value>) {
+    let mut new = new.into();
+    match item.as_value() {
+        Some(old) => *new.decor_mut() = old.decor().clone(),
+        None => new.decor_mut().clear(),
+    }
+    *item = Item::Value(new);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::test_utils::load;
+
+    use super::*;
+
+    fn edited(
+        files_names: &[&str],
+        feusic_toml: &str,
+        edit: impl FnOnce(&mut Vec<Start>, &mut Vec<Vec<Next>>),
+    ) -> String {
+        let mut feusic = load(files_names, feusic_toml).unwrap();
+        let saved_start = feusic.start.clone();
+        let saved_transitions = feusic.transitions();
+
+        let mut start = saved_start.clone();
+        let mut transitions = saved_transitions.clone();
+        edit(&mut start, &mut transitions);
+        feusic.start = start;
+        feusic.set_transitions(transitions.clone());
+
+        let edited = feusic
+            .edit_feusic_toml(feusic_toml, &saved_start, &saved_transitions)
+            .unwrap();
+
+        let reloaded = load(files_names, &edited).unwrap();
+        assert_eq!(reloaded.start, feusic.start);
+        assert_eq!(reloaded.transitions(), transitions);
+
+        edited
+    }
+
+    #[test]
+    fn edits_only_the_changed_musics_of_the_timing() {
+        let feusic_toml = r#"# Town theme
+timing = "s@map|@map:w5000:@town|@town:w2m;p30:@map/w1m;p70:@town" # edited
+duration = 600
+fade = 2000
+
+[aliases]
+town = "town.mp3"
+"#;
+
+        let edited = edited(&["map.mp3", "town.mp3"], feusic_toml, |_, transitions| {
+            transitions[0][0].wait.1 = Wait::millis(8000)
+        });
+
+        assert_eq!(
+            edited,
+            feusic_toml.replace("@map:w5000:@town", "0:w5000-8000:1")
+        );
+    }
+
+    #[test]
+    fn edits_only_the_changed_keys_of_the_tables() {
+        let feusic_toml = r#"start = "map"
+duration = 600
+
+[aliases]
+boss = "battle.mp3"
+
+# The map leads to the boss
+[[music]]
+index = "map"
+cooldown = 5000
+
+[[music.next]]
+target = "boss"
+wait_min = "2m"
+stinger = "cymbal.wav"
+
+[[music]]
+index = "boss"
+next = [{ target = "map", wait_min = 1000 }]
+"#;
+
+        let edited = edited(
+            &["battle.mp3", "cymbal.wav", "map.mp3"],
+            feusic_toml,
+            |start, transitions| {
+                start[0].node = 0;
+                transitions[1][0].probability_weight = 60;
+                let added = Next {
+                    target_music: 0,
+                    wait: (Wait::millis(1000), Wait::millis(3000)),
+                    probability_weight: 40,
+                    stinger: None,
+                    ..transitions[1][0].clone()
+                };
+                transitions[1].push(added);
+                transitions[0][0].wait.0 = Wait::millis(500);
+            },
+        );
+
+        assert_eq!(
+            edited,
+            r#"start = 0
+duration = 600
+
+[aliases]
+boss = "battle.mp3"
+
+# The map leads to the boss
+[[music]]
+index = "map"
+cooldown = 5000
+
+[[music.next]]
+target = "boss"
+wait_min = "2m"
+stinger = "cymbal.wav"
+weight = 60
+
+[[music.next]]
+target = 0
+wait_min = 1000
+wait_max = 3000
+weight = 40
+
+[[music]]
+index = "boss"
+next = [{ target = "map", wait_min = 500, wait_max = 1000 }]
+"#
+        );
+    }
+}
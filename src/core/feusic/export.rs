@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+use super::{
+    timing::{Wait, WaitUnit},
+    Feusic,
+};
+
+/// Text format of the transition graph of a feusic, see [`Feusic::to_graph`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+/// Transition between two nodes of the graph, with its label.
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<String>,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "unknown graph format {}, expected dot or mermaid",
+                s
+            )),
+        }
+    }
+}
+
+impl<M> Feusic<M> {
+    /// The musics, or states in layered mode, and their transitions as a graph in `format`.
+    /// Transitions are labelled with their probability among the ones of their music and
+    /// their wait. The start is a node of its own pointing to the musics the feusic starts
+    /// from, labelled with their probability when there are several.
+    pub fn to_graph(&self, format: GraphFormat) -> String {
+        let nodes = self
+            .node_titles()
+            .into_iter()
+            .enumerate()
+            .map(|(node, title)| (node_id(node), title))
+            .collect::<Vec<_>>();
+
+        let start_total = self
+            .start
+            .iter()
+            .map(|start| start.probability_weight)
+            .sum::<usize>();
+        let start_edges = self.start.iter().map(|start| Edge {
+            from: "start".to_string(),
+            to: node_id(start.node),
+            label: (self.start.len() > 1)
+                .then(|| percentage(start.probability_weight, start_total)),
+        });
+
+        let transitions = self.transitions();
+        let transition_edges = transitions.iter().enumerate().flat_map(|(node, choices)| {
+            let total = choices
+                .iter()
+                .map(|next| next.probability_weight)
+                .sum::<usize>();
+            choices.iter().map(move |next| Edge {
+                from: node_id(node),
+                to: node_id(next.target_music),
+                label: Some(format!(
+                    "{}, wait {}",
+                    percentage(next.probability_weight, total),
+                    wait_range(&next.wait)
+                )),
+            })
+        });
+
+        let edges = start_edges.chain(transition_edges).collect::<Vec<_>>();
+
+        match format {
+            GraphFormat::Dot => to_dot(self.display_name(), &nodes, &edges),
+            GraphFormat::Mermaid => to_mermaid(&nodes, &edges),
+        }
+    }
+}
+
+fn to_dot(name: &str, nodes: &[(String, String)], edges: &[Edge]) -> String {
+    let mut lines = vec![
+        format!("digraph \"{}\" {{", escape_dot(name)),
+        "    start [shape=point];".to_string(),
+    ];
+    for (id, title) in nodes {
+        lines.push(format!("    {} [label=\"{}\"];", id, escape_dot(title)));
+    }
+    for edge in edges {
+        match &edge.label {
+            Some(label) => lines.push(format!(
+                "    {} -> {} [label=\"{}\"];",
+                edge.from,
+                edge.to,
+                escape_dot(label)
+            )),
+            None => lines.push(format!("    {} -> {};", edge.from, edge.to)),
+        }
+    }
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+fn to_mermaid(nodes: &[(String, String)], edges: &[Edge]) -> String {
+    let mut lines = vec!["flowchart LR".to_string(), "    start((start))".to_string()];
+    for (id, title) in nodes {
+        lines.push(format!("    {}[\"{}\"]", id, escape_mermaid(title)));
+    }
+    for edge in edges {
+        match &edge.label {
+            Some(label) => lines.push(format!(
+                "    {} -->|\"{}\"| {}",
+                edge.from,
+                escape_mermaid(label),
+                edge.to
+            )),
+            None => lines.push(format!("    {} --> {}", edge.from, edge.to)),
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn node_id(node: usize) -> String {
+    format!("n{}", node)
+}
+
+/// `weight` out of `total` as a percentage rounded to one decimal, e.g. `33.3%`.
+fn percentage(weight: usize, total: usize) -> String {
+    if total == 0 {
+        return "0%".to_string();
+    }
+    let percentage = weight as f64 * 100.0 / total as f64;
+    format!("{}%", (percentage * 10.0).round() / 10.0)
+}
+
+/// Waits like in the timing, with `ms` after the ones in milliseconds, e.g. `5000ms-8000ms`
+/// or `2m`.
+fn wait_range((wait_min, wait_max): &(Wait, Wait)) -> String {
+    let wait = |wait: &Wait| match wait.unit {
+        WaitUnit::Milliseconds => format!("{}ms", wait),
+        _ => wait.to_string(),
+    };
+    if wait_min == wait_max {
+        wait(wait_min)
+    } else {
+        format!("{}-{}", wait(wait_min), wait(wait_max))
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::{loader::FeusicMusicLoader, test_utils::load};
+
+    use super::*;
+
+    fn feusic() -> Feusic<FeusicMusicLoader> {
+        load(
+            &["calm.mp3", "tense \"boss\".mp3"],
+            r#"
+                timing = "s0;p3/1;p1|0:w50000-80000;p30:1/w2m;p10:0|1:w4L:0"
+                duration = 600
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn graph_to_dot() {
+        assert_eq!(
+            feusic().to_graph(GraphFormat::Dot),
+            [
+                "digraph \"feusic\" {",
+                "    start [shape=point];",
+                "    n0 [label=\"calm.mp3\"];",
+                "    n1 [label=\"tense \\\"boss\\\".mp3\"];",
+                "    start -> n0 [label=\"75%\"];",
+                "    start -> n1 [label=\"25%\"];",
+                "    n0 -> n1 [label=\"75%, wait 50000ms-80000ms\"];",
+                "    n0 -> n0 [label=\"25%, wait 2m\"];",
+                "    n1 -> n0 [label=\"100%, wait 4L\"];",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn graph_to_mermaid() {
+        assert_eq!(
+            feusic().to_graph(GraphFormat::Mermaid),
+            [
+                "flowchart LR",
+                "    start((start))",
+                "    n0[\"calm.mp3\"]",
+                "    n1[\"tense #quot;boss#quot;.mp3\"]",
+                "    start -->|\"75%\"| n0",
+                "    start -->|\"25%\"| n1",
+                "    n0 -->|\"75%, wait 50000ms-80000ms\"| n1",
+                "    n0 -->|\"25%, wait 2m\"| n0",
+                "    n1 -->|\"100%, wait 4L\"| n0",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn unknown_graph_format() {
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{error::Error, str::FromStr};
+
+use toml::{Table, Value};
+use toml_edit::DocumentMut;
+
+use super::{audio_files_names, config::FeusicConfig, edit};
+
+/// Version of feusic.toml written by this Feusic Player.
+///
+/// - 0: no `format_version`, musics ordered by file name.
+/// - 1: `musics` always lists the order of the musics.
+/// - 2: layered `[[state]]` tables, intro, endings, stingers, gains, parameters, constraints
+///   of the transitions, waits with units and weighted starts. Unknown keys are rejected.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// First version rejecting the keys that are not part of the format, older feusic.toml keep
+/// loading with them.
+pub(super) const STRICT_FORMAT_VERSION: u32 = 2;
+
+/// Reads the `format_version` of a feusic.toml and upgrades it one version at a time up to
+/// [`FORMAT_VERSION`]. Returns whether the table changed.
+///
+/// `audio_files_names` are the audio files in the feusic, older versions need them to
+/// keep the musics in the same order.
+pub(super) fn upgrade(
+    table: &mut Table,
+    audio_files_names: &[String],
+) -> Result<bool, Box<dyn Error>> {
+    let mut version = format_version(table)?;
+
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "feusic.toml has format_version {} but this Feusic Player supports up to {}, please update it",
+            version, FORMAT_VERSION
+        )
+        .into());
+    }
+
+    let upgraded = version < FORMAT_VERSION;
+    while version < FORMAT_VERSION {
+        match version {
+            0 => upgrade_v0(table, audio_files_names),
+            // Version 2 only added keys, a version 1 feusic.toml means the same.
+            1 => {}
+            _ => unreachable!("no upgrade from format_version {}", version),
+        }
+        version += 1;
+        table.insert("format_version".to_string(), Value::Integer(version as i64));
+    }
+
+    Ok(upgraded)
+}
+
+/// Rewrites a feusic.toml to the latest [`FORMAT_VERSION`], `None` when it already is.
+pub fn migrate_feusic_toml(
+    feusic_toml: &str,
+    audio_files_names: &[String],
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut table: Table =
+        toml::from_str(feusic_toml).map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+
+    if !upgrade(&mut table, audio_files_names)? {
+        return Ok(None);
+    }
+
+    // The latest version rejects unknown keys, they would make the migrated file fail to load.
+    table
+        .clone()
+        .try_into::<FeusicConfig>()
+        .map_err(|e| format!("Failed to read feusic.toml. {}", e))?
+        .reject_unknown_keys()?;
+
+    Ok(Some(toml::to_string(&table)?))
+}
+
+/// Rewrites a feusic.toml setting the `gain_db` of its musics, `gains_db` are in music index
+/// order and `None` leaves the gain of a music as it is. The rest of the file stays as it is,
+/// a music without a `[[music]]` table gets one.
+pub fn set_music_gains(
+    feusic_toml: &str,
+    files_names: &[String],
+    gains_db: &[Option<f64>],
+) -> Result<String, Box<dyn Error>> {
+    let audio_files_names = audio_files_names(files_names);
+    let config = FeusicConfig::from_toml(feusic_toml, &audio_files_names)?;
+    let names = config.music_names(&config.ordered_musics(&audio_files_names)?)?;
+    let mut document = DocumentMut::from_str(feusic_toml)
+        .map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+
+    for (music_index, gain_db) in gains_db.iter().enumerate() {
+        let Some(gain_db) = gain_db else {
+            continue;
+        };
+
+        let music = edit::music_table(&mut document, &names, music_index)?;
+        edit::set_value(&mut music["gain_db"], *gain_db);
+    }
+
+    Ok(document.to_string())
+}
+
+pub(super) fn format_version(table: &Table) -> Result<u32, Box<dyn Error>> {
+    let Some(version) = table.get("format_version") else {
+        return Ok(0);
+    };
+
+    version
+        .as_integer()
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or_else(|| {
+            format!(
+                "`format_version` should be a positive integer, found {}",
+                version
+            )
+            .into()
+        })
+}
+
+/// Version 0 ordered the musics by file name, pin that order in `musics`.
+/// The `intro`, the endings and the stingers are not musics and stay out of the list.
+fn upgrade_v0(table: &mut Table, audio_files_names: &[String]) {
+    if table.contains_key("musics") {
+        return;
+    }
+
+    let clips = clip_names(table);
+    let mut musics_names = audio_files_names
+        .iter()
+        .filter(|name| !clips.contains(&name.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    musics_names.sort();
+    table.insert(
+        "musics".to_string(),
+        Value::Array(musics_names.into_iter().map(Value::String).collect()),
+    );
+}
+
+/// File names of the `intro`, of the endings and of the stingers of the `[[music.next]]` and
+/// `[[state.next]]` tables.
+fn clip_names(table: &Table) -> Vec<&str> {
+    let intro = table.get("intro").and_then(Value::as_str);
+    let ending = table.get("ending").and_then(Value::as_str);
+    let music_endings = table
+        .get("music")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|music| music.get("ending")?.as_str());
+    let stingers = ["music", "state"]
+        .iter()
+        .filter_map(|kind| table.get(*kind)?.as_array())
+        .flatten()
+        .filter_map(|node| node.get("next")?.as_array())
+        .flatten()
+        .filter_map(|next| next.get("stinger")?.as_str());
+
+    intro
+        .into_iter()
+        .chain(ending)
+        .chain(music_endings)
+        .chain(stingers)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrates_v0_pinning_the_musics_order() {
+        let audio_files_names = vec!["b.mp3".to_string(), "a.ogg".to_string()];
+
+        let migrated = migrate_feusic_toml(
+            "timing = \"s0|0:w1000:1|1:w1000:0\"\nduration = 600\n",
+            &audio_files_names,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            migrated,
+            "duration = 600\nformat_version = 2\nmusics = [\"a.ogg\", \"b.mp3\"]\ntiming = \"s0|0:w1000:1|1:w1000:0\"\n"
+        );
+        assert_eq!(
+            migrate_feusic_toml(&migrated, &audio_files_names).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn sets_the_gains_of_the_musics() {
+        let files_names = vec![
+            "a.mp3".to_string(),
+            "b.mp3".to_string(),
+            "c.mp3".to_string(),
+        ];
+
+        let feusic_toml = set_music_gains(
+            r#"format_version = 1
+timing = "s0|0:w1000:1|1:w1000:2|2:w1000:0" # a, b then c
+duration = 600
+
+# The battle is louder
+[[music]]
+index = "b"
+gain_db = 2.0 # measured
+title = "Battle"
+
+[[music]]
+index = 0
+title = "Map"
+"#,
+            &files_names,
+            &[Some(-3.5), Some(1.5), Some(-1.0)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            feusic_toml,
+            r#"format_version = 1
+timing = "s0|0:w1000:1|1:w1000:2|2:w1000:0" # a, b then c
+duration = 600
+
+# The battle is louder
+[[music]]
+index = "b"
+gain_db = 1.5 # measured
+title = "Battle"
+
+[[music]]
+index = 0
+title = "Map"
+gain_db = -3.5
+
+[[music]]
+index = 2
+gain_db = -1.0
+"#
+        );
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let error = migrate_feusic_toml("format_version = 3\nduration = 600\n", &[]).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "feusic.toml has format_version 3 but this Feusic Player supports up to 2, please update it"
+        );
+
+        let error = migrate_feusic_toml(
+            "format_version = 4294967298
+duration = 600
+",
+            &[],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`format_version` should be a positive integer, found 4294967298"
+        );
+    }
+}
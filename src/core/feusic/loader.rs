@@ -2,9 +2,27 @@ use std::{
     error::Error,
     fs::File,
     io::{BufReader, Cursor, Read, Seek},
+    path::Path,
 };
 use zip::ZipArchive;
 
+/// Extensions of the audio files that can be played, on their own or inside a feusic.
+pub const SUPPORTED_AUDIO_EXTENSIONS: [&str; 5] = ["mp3", "ogg", "oga", "flac", "wav"];
+
+/// Whether `extension` is one of [`SUPPORTED_AUDIO_EXTENSIONS`], ignoring the case.
+pub fn is_supported_audio_extension(extension: &str) -> bool {
+    SUPPORTED_AUDIO_EXTENSIONS
+        .iter()
+        .any(|supported| supported.eq_ignore_ascii_case(extension))
+}
+
+/// Whether the file at `path`, or named `path` inside a zip, is a supported audio file.
+pub fn is_supported_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(is_supported_audio_extension)
+}
+
 pub trait MusicLoader: Send + Sync + 'static {
     type Reader: Read + Seek + Sync + Send;
 
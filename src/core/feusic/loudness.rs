@@ -0,0 +1,258 @@
+use std::{error::Error, f64::consts::PI};
+
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as SymphoniaError,
+};
+
+use super::{decode::open_format, loader::MusicLoader};
+
+/// Loudness below which a block is silence and is not measured, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks quieter than the ungated loudness by more than this are not measured, in LU.
+const RELATIVE_GATE: f64 = -10.0;
+const SEGMENTS_PER_BLOCK: usize = 4;
+
+/// Integrated loudness meter following ITU-R BS.1770: K-weighted mean square over 400ms
+/// blocks overlapping by 75%, gated to ignore silence and quiet passages.
+///
+/// All the channels are weighted as front channels, stems are expected to be mono or stereo.
+pub struct LoudnessMeter {
+    channels: Vec<KWeighting>,
+    segment_length: usize,
+    segment_samples: usize,
+    segment_energy: f64,
+    /// Mean square of every 100ms segment, summed across channels.
+    segments: Vec<f64>,
+}
+
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        Self {
+            channels: (0..channels)
+                .map(|_| KWeighting::new(sample_rate))
+                .collect(),
+            segment_length: (sample_rate / 10) as usize,
+            segment_samples: 0,
+            segment_energy: 0.0,
+            segments: vec![],
+        }
+    }
+
+    /// Adds interleaved samples.
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels.len()) {
+            for (sample, weighting) in frame.iter().zip(self.channels.iter_mut()) {
+                let weighted = weighting.process(*sample as f64);
+                self.segment_energy += weighted * weighted;
+            }
+
+            self.segment_samples += 1;
+            if self.segment_samples == self.segment_length {
+                self.segments
+                    .push(self.segment_energy / self.segment_length as f64);
+                self.segment_samples = 0;
+                self.segment_energy = 0.0;
+            }
+        }
+    }
+
+    /// Integrated loudness in LUFS, `None` when shorter than a block or silent.
+    pub fn integrated(&self) -> Option<f64> {
+        let blocks = self
+            .segments
+            .windows(SEGMENTS_PER_BLOCK)
+            .map(|segments| segments.iter().sum::<f64>() / SEGMENTS_PER_BLOCK as f64)
+            .filter(|energy| loudness(*energy) > ABSOLUTE_GATE)
+            .collect::<Vec<_>>();
+
+        let relative_gate = loudness(mean(&blocks)?) + RELATIVE_GATE;
+        let gated_blocks = blocks
+            .into_iter()
+            .filter(|energy| loudness(*energy) > relative_gate)
+            .collect::<Vec<_>>();
+
+        Some(loudness(mean(&gated_blocks)?))
+    }
+}
+
+impl KWeighting {
+    /// Filters of the K-weighting computed for the sample rate, at 48kHz they match the
+    /// coefficients given in BS.1770.
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            shelf: Biquad::high_shelf(sample_rate),
+            high_pass: Biquad::high_pass(sample_rate),
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+impl Biquad {
+    fn high_shelf(sample_rate: u32) -> Self {
+        let q = 0.7071752369554196;
+        let k = (PI * 1681.974450955533 / sample_rate as f64).tan();
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+
+        Self::normalized(
+            [
+                vh + vb * k / q + k * k,
+                2.0 * (k * k - vh),
+                vh - vb * k / q + k * k,
+            ],
+            [
+                1.0 + k / q + k * k,
+                2.0 * (k * k - 1.0),
+                1.0 - k / q + k * k,
+            ],
+        )
+    }
+
+    fn high_pass(sample_rate: u32) -> Self {
+        let q = 0.5003270373238773;
+        let k = (PI * 38.13547087602444 / sample_rate as f64).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Self::normalized(
+            [a0, -2.0 * a0, a0],
+            [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    fn normalized(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// Decodes the whole music and measures its integrated loudness in LUFS, `None` when it is
+/// too short or silent.
+pub fn measure_loudness<M: MusicLoader>(
+    loader: &M,
+    music_name: &str,
+) -> Result<Option<f64>, Box<dyn Error>> {
+    let mut format = open_format(loader, music_name)?;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| format!("{} has no audio track", music_name))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| format!("{} has no sample rate", music_name))?;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut meter = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(e)) => {
+                eprintln!("Skipping packet of {}: {}", music_name, e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        samples.copy_interleaved_ref(decoded);
+
+        meter
+            .get_or_insert_with(|| LoudnessMeter::new(spec.channels.count(), sample_rate))
+            .push(samples.samples());
+    }
+
+    Ok(meter.and_then(|meter| meter.integrated()))
+}
+
+fn loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f64, amplitude: f64, sample_rate: u32, seconds: f64) -> Vec<f32> {
+        (0..(sample_rate as f64 * seconds) as usize)
+            .map(|i| {
+                (amplitude * (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin()) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_scale_1khz_sine_is_about_minus_3_lufs() {
+        let mut meter = LoudnessMeter::new(1, 48000);
+        meter.push(&sine(1000.0, 1.0, 48000, 5.0));
+
+        let loudness = meter.integrated().unwrap();
+        assert!((loudness - -3.0).abs() < 0.1, "{}", loudness);
+    }
+
+    #[test]
+    fn halving_the_amplitude_lowers_the_loudness_by_6_db() {
+        let mut loud = LoudnessMeter::new(1, 44100);
+        loud.push(&sine(440.0, 0.5, 44100, 3.0));
+        let mut quiet = LoudnessMeter::new(1, 44100);
+        quiet.push(&sine(440.0, 0.25, 44100, 3.0));
+
+        let difference = loud.integrated().unwrap() - quiet.integrated().unwrap();
+        assert!((difference - 6.02).abs() < 0.05, "{}", difference);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let mut meter = LoudnessMeter::new(2, 48000);
+        meter.push(&vec![0.0; 48000 * 2 * 2]);
+
+        assert_eq!(meter.integrated(), None);
+    }
+}
@@ -1,27 +1,90 @@
 use std::{
     error::Error,
     fs::{self, DirEntry, File},
-    io::Read,
-    iter::Peekable,
-    path::PathBuf,
-    str::Chars,
+    io::{Read, Write},
+    path::{Path, PathBuf},
     time::Duration,
 };
 
-use loader::FeusicMusicLoader;
-use serde::Deserialize;
-
+use config::FeusicConfig;
+use loader::{is_supported_audio_file, FeusicMusicLoader};
+use parameters::{Condition, Parameter};
+use selection::Constraints;
+use serde::{Deserialize, Serialize};
+use tempo::Tempo;
+use timing::Wait;
+use validation::{InvalidFeusicError, Severity};
+
+mod config;
+pub mod decode;
+pub mod edit;
+pub mod export;
+pub mod format;
 pub mod loader;
+pub mod loudness;
+mod names;
+pub mod parameters;
+pub mod selection;
+pub mod simulation;
+pub mod tempo;
+#[cfg(test)]
+pub(crate) mod test_utils;
+pub mod timing;
+pub mod validation;
 
 #[derive(Debug)]
 pub struct Feusic<M> {
     pub name: String,
     pub musics: Vec<Music<M>>,
-    pub first_music: usize,
+    /// Musics the feusic can start from, or states in layered mode, one of them is chosen by
+    /// weight each time the feusic begins.
+    pub start: Vec<Start>,
+    /// States of a layered feusic, empty when the feusic plays one music at a time.
+    /// In layered mode the transitions go from state to state and the musics have none.
+    pub states: Vec<LayerState>,
     pub looping: Looping,
+    pub tempo: Option<Tempo>,
+    /// Fade of the transitions that do not define their own.
+    pub fade: Fade,
+    pub meta: FeusicMeta,
+    /// Loader of the [`FeusicMeta::cover`] image.
+    pub cover: Option<M>,
+    /// Played once before the start music, it does not loop and is not part of the musics.
+    pub intro: Option<Clip<M>>,
+    /// Clips played by the transitions, see [`Stinger`].
+    pub stingers: Vec<Clip<M>>,
+    /// Played once when the duration expires, instead of letting the music play to its end.
+    /// Musics can have their own, see [`Feusic::ending_of`].
+    pub ending: Option<Clip<M>>,
+    /// Crossfade into the ending, zero cuts into it.
+    pub ending_fade: Duration,
+    /// Values the transitions can depend on, set while the feusic plays.
+    pub parameters: Vec<Parameter>,
 }
 
-#[derive(Debug)]
+/// Descriptive information of a feusic, the `[meta]` table of feusic.toml.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FeusicMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub composer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Path of the cover image inside the feusic.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    unknown: config::UnknownKeys,
+}
+
+#[derive(Debug, PartialEq)]
 pub enum Looping {
     Whole(Duration),
     Partial {
@@ -32,87 +95,140 @@ pub enum Looping {
     None,
 }
 
+/// Region of a music that loops, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopRegion {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Audio file of a feusic that is played once, outside of the looping musics.
+#[derive(Debug)]
+pub struct Clip<M> {
+    pub name: String,
+    pub loader: M,
+}
+
 #[derive(Debug)]
 pub struct Music<M> {
     pub name: String,
+    /// Display name of the music, the file name is shown when missing.
+    pub title: Option<String>,
     pub loader: M,
     pub next_choices: Vec<Next>,
+    /// Overrides the loop region of the feusic for this music.
+    pub loop_region: Option<LoopRegion>,
+    /// Volume of the music when it is the one playing, to even out stems of different sources.
+    pub gain_db: f64,
+    /// Played instead of the feusic ending when the duration expires during this music.
+    pub ending: Option<Clip<M>>,
+    /// Whether the transitions of the music are drawn from a shuffle bag.
+    pub shuffle: bool,
+}
+
+/// Musics playing together in a layered feusic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerState {
+    pub name: String,
+    /// Volume in dB of every music in the state, `None` is a music that is not playing.
+    pub layers: Vec<Option<f64>>,
+    pub next_choices: Vec<Next>,
+    /// Whether the transitions of the state are drawn from a shuffle bag.
+    pub shuffle: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Next {
     pub probability_weight: usize,
     pub target_music: usize,
-    pub wait: (usize, usize),
+    /// Bounds of the random wait before the transition.
+    pub wait: (Wait, Wait),
+    pub fade: Fade,
+    pub stinger: Option<Stinger>,
+    /// Condition on the [`Feusic::parameters`], the transition only happens when it holds.
+    pub when: Option<Condition>,
+    /// Limits on how often the transition is chosen, see [`selection::TransitionHistory`].
+    pub constraints: Constraints,
+}
+
+/// Music, or state in layered mode, a feusic can start from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Start {
+    pub node: usize,
+    pub probability_weight: usize,
 }
 
-#[derive(Deserialize)]
-struct FeusicConfig {
-    timing: String,
-    duration: u64,
-    loop_start: Option<f64>,
-    loop_end: Option<f64>,
+/// Clip played once on top of the musics when a transition starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stinger {
+    /// Index of the clip in [`Feusic::stingers`].
+    pub clip: usize,
+    /// Volume change in dB of the musics fading out, applied when the stinger starts.
+    pub duck_db: f64,
+}
+
+pub const DEFAULT_FADE_DURATION: Duration = Duration::from_millis(1000);
+
+/// How the current music fades out while the next one fades in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fade {
+    pub duration: Duration,
+    pub easing: FadeEasing,
+}
+
+/// Curve of a fade, `linear` or `{ power = 0.15 }` in feusic.toml.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeEasing {
+    Linear,
+    Power(f64),
 }
 
 impl Feusic<FeusicMusicLoader> {
     pub fn from_feusic_zip_file(file_path: &PathBuf, file: &File) -> Result<Self, Box<dyn Error>> {
-        println!("Parsing {:?}", file_path);
+        log!("Parsing {:?}", file_path);
         let mut zip = zip::ZipArchive::new(file)?;
 
-        let musics_names = zip
-            .file_names()
-            .filter(|f| f.ends_with(".mp3"))
-            .map(|n| n.to_string())
-            .collect::<Vec<_>>();
+        let files_names = zip.file_names().map(|n| n.to_string()).collect::<Vec<_>>();
 
         let mut feusic_toml = String::new();
         zip.by_name("feusic.toml")
             .map_err(|e| format!("feusic.toml should be in a .feusic file. {}", e))?
             .read_to_string(&mut feusic_toml)?;
 
-        let feusic_path = file_path.file_name().unwrap().to_str().unwrap().to_string();
+        let feusic_name = file_path.file_name().unwrap().to_str().unwrap().to_string();
+        let feusic_path = file_path.to_str().unwrap().to_string();
 
-        Self::from_feusic(
-            feusic_path.clone(),
-            &musics_names,
-            feusic_toml,
-            |_, music_name| FeusicMusicLoader::ZipFeusic {
+        Self::from_feusic(feusic_name, &files_names, feusic_toml, |music_name| {
+            FeusicMusicLoader::ZipFeusic {
                 feusic_path: feusic_path.clone(),
                 music_name,
-            },
-        )
-        .inspect(|feusic| println!("Loaded musics {:?}", feusic.musics))
+            }
+        })
+        .inspect(|feusic| log!("Loaded musics {:?}", feusic.musics))
     }
 
     pub fn from_feusic_folder(folder_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
-        println!("Parsing folder {:?}", folder_path);
+        log!("Parsing folder {:?}", folder_path);
 
-        let mut files = fs::read_dir(folder_path)
+        let files = fs::read_dir(folder_path)
             .map_err(|e| format!("Folder path should exist. {}", e))?
             .filter_map(|entry| entry.ok())
             .filter(|entry| entry.path().is_file())
             .collect::<Vec<DirEntry>>();
 
-        files.sort_by_key(|file| file.path());
-
-        let mut musics_paths = vec![];
-        let mut musics_names = vec![];
+        let mut files_names = vec![];
         let mut feusic_toml_file = None;
 
         for entry in files {
             let path = entry.path();
-            let extension = path.extension();
-            println!("Checking {:?} in folder {:?}", path, folder_path);
-
-            if let Some(ext) = extension {
-                if ext == "mp3" {
-                    musics_paths.push(path.to_str().unwrap().to_string());
-                    musics_names.push(path.file_name().unwrap().to_str().unwrap().to_string());
-                } else if let Some(name) = path.file_name() {
-                    if name == "feusic.toml" {
-                        feusic_toml_file = Some(File::open(&entry.path())?);
-                    }
+            log!("Checking {:?} in folder {:?}", path, folder_path);
+
+            if let Some(name) = path.file_name() {
+                if name == "feusic.toml" {
+                    feusic_toml_file = Some(File::open(&entry.path())?);
                 }
+                files_names.push(name.to_str().unwrap().to_string());
             }
         }
 
@@ -128,45 +244,163 @@ impl Feusic<FeusicMusicLoader> {
             .unwrap()
             .to_string();
 
-        Self::from_feusic(feusic_path, &musics_names, feusic_toml, |music_index, _| {
+        Self::from_feusic(feusic_path, &files_names, feusic_toml, |music_name| {
             FeusicMusicLoader::FolderFeusic {
-                music_path: musics_paths[music_index].clone(),
+                music_path: folder_path.join(music_name).to_str().unwrap().to_string(),
             }
         })
-        .inspect(|feusic| println!("Loaded musics {:?}", feusic.musics))
+        .inspect(|feusic| log!("Loaded musics {:?}", feusic.musics))
+    }
+
+    /// Loads a `.feusic` zip file or a `.feusic` folder.
+    pub fn from_feusic_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = path.to_path_buf();
+        if path.is_dir() {
+            Self::from_feusic_folder(&path)
+        } else {
+            let file = File::open(&path).map_err(|e| format!("cannot open {:?}. {}", path, e))?;
+            Self::from_feusic_zip_file(&path, &file)
+        }
     }
 
     pub fn from_audio_file(file_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
-        println!("Parsing {:?}", file_path);
+        log!("Parsing {:?}", file_path);
         let filename = file_path.file_name().unwrap().to_str().unwrap().to_string();
 
         Ok(Self {
-            first_music: 0,
+            start: vec![Start {
+                node: 0,
+                probability_weight: 100,
+            }],
+            states: vec![],
             looping: Looping::None,
+            tempo: None,
+            fade: Fade::default(),
+            meta: FeusicMeta::default(),
+            cover: None,
+            intro: None,
+            stingers: vec![],
+            ending: None,
+            ending_fade: DEFAULT_FADE_DURATION,
+            parameters: vec![],
             name: filename.clone(),
             musics: vec![Music {
                 name: filename,
+                title: None,
                 next_choices: vec![],
+                loop_region: None,
+                gain_db: 0.0,
+                ending: None,
+                shuffle: false,
                 loader: FeusicMusicLoader::FolderFeusic {
                     music_path: file_path.to_str().unwrap().to_string(),
                 },
             }],
         })
-        .inspect(|feusic| println!("Loaded musics {:?}", feusic.musics))
+        .inspect(|feusic| log!("Loaded musics {:?}", feusic.musics))
     }
 
-    fn from_feusic<F: Fn(usize, String) -> FeusicMusicLoader>(
+    /// Builds a feusic from its feusic.toml and the names of the files it contains.
+    /// The musics are in the order of the `musics` list when defined, otherwise sorted by
+    /// file name, so the same feusic gets the same indices whatever its packaging.
+    fn from_feusic<F: Fn(String) -> FeusicMusicLoader>(
         feusic_name: String,
-        musics_names: &Vec<String>,
+        files_names: &[String],
         feusic_toml: String,
         music_loader_factory: F,
     ) -> Result<Self, Box<dyn Error>> {
-        let config: FeusicConfig = toml::from_str(&feusic_toml)
-            .map_err(|e| format!("Failed to read feusic.toml. {}", e))?;
+        let audio_files_names = audio_files_names(files_names);
+        let config = FeusicConfig::from_toml(&feusic_toml, &audio_files_names)?;
+
+        let musics_names = config.ordered_musics(&audio_files_names)?;
+        let clips = config.clips();
+        for ignored in audio_files_names.iter().filter(|name| {
+            !musics_names.contains(name) && !clips.iter().any(|(_, clip)| clip == name)
+        }) {
+            log!(
+                "{}: {} is not in `musics` and is ignored",
+                feusic_name,
+                ignored
+            );
+        }
 
-        let parsed_timing = ParsedTiming::try_from(&config.timing.as_str())?;
+        let names = config.music_names(&musics_names)?;
+        let (start, timing_musics, states) = match config.layer_states(&names)? {
+            Some(layer_states) => (layer_states.start, vec![], layer_states.states),
+            None => {
+                let (start, timing_musics) = config.timing_musics(&names)?;
+                (start, timing_musics, vec![])
+            }
+        };
 
-        Ok(Self {
+        let music_tables = config.music_tables(&names)?;
+        let musics_constraints = music_tables
+            .iter()
+            .map(|music_table| match music_table {
+                Some(music_table) => music_table.constraints(),
+                None => Ok(Constraints::default()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let default_fade = config.default_fade();
+        let mut musics_next_choices = vec![None; musics_names.len()];
+        let mut duplicated_transitions = vec![];
+        for parsed_timing_music in timing_musics {
+            let next_choices = musics_next_choices
+                .get_mut(parsed_timing_music.music_index)
+                .ok_or_else(|| format!("No music at index {}", parsed_timing_music.music_index))?;
+
+            if next_choices.is_some() {
+                duplicated_transitions.push(parsed_timing_music.music_index);
+                continue;
+            }
+
+            *next_choices = Some(
+                parsed_timing_music
+                    .choices
+                    .into_iter()
+                    .map(|parsed_choice| parsed_choice.into_next(default_fade, &musics_constraints))
+                    .collect::<Vec<Next>>(),
+            );
+        }
+
+        let cover = match &config.meta.cover {
+            Some(cover) if files_names.contains(cover) => Some(music_loader_factory(cover.clone())),
+            Some(cover) => {
+                return Err(format!("[meta] cover: {} is not in the feusic", cover).into())
+            }
+            None => None,
+        };
+
+        let clip = |name: &str, key: &str| {
+            if !audio_files_names.iter().any(|audio| audio == name) {
+                return Err(format!(
+                    "{}: {} is not an audio file of the feusic",
+                    key, name
+                ));
+            }
+            Ok(Clip {
+                name: name.to_string(),
+                loader: music_loader_factory(name.to_string()),
+            })
+        };
+
+        let intro = config
+            .intro
+            .as_ref()
+            .map(|intro| clip(intro, "`intro`"))
+            .transpose()?;
+        let ending = config
+            .ending
+            .as_ref()
+            .map(|ending| clip(ending, "`ending`"))
+            .transpose()?;
+        let stingers = config
+            .stingers()
+            .into_iter()
+            .map(|stinger| clip(stinger, "stinger"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let feusic = Self {
             name: feusic_name,
             looping: match (config.loop_start, config.loop_end) {
                 (Some(start), Some(end)) => Looping::Partial {
@@ -176,229 +410,308 @@ impl Feusic<FeusicMusicLoader> {
                 },
                 _ => Looping::Whole(Duration::from_secs(config.duration)),
             },
-            first_music: parsed_timing.first_music_index,
-            musics: parsed_timing
-                .timing_musics
-                .map(|parsed_timing_music| {
-                    let parsed_timing_music = match parsed_timing_music {
-                        Ok(pc) => pc,
-                        Err(e) => return Err(e),
-                    };
-
-                    if parsed_timing_music
-                        .choices
-                        .iter()
-                        .any(|c| c.target_music_index >= musics_names.len())
-                    {
-                        return Err(format!(
-                            "target index {} does not exists",
-                            parsed_timing_music
-                                .choices
-                                .iter()
-                                .find(|c| musics_names.len() < c.target_music_index)
-                                .unwrap()
-                                .target_music_index
-                        )
-                        .into());
-                    }
-
-                    let name = musics_names
-                        .get(parsed_timing_music.music_index)
-                        .map(|s| s.to_string())
-                        .ok_or_else(|| {
-                            format!("No music at index {}", parsed_timing_music.music_index)
-                        })?;
-
+            start,
+            states,
+            tempo: config.tempo()?,
+            fade: default_fade,
+            meta: config.meta.clone(),
+            cover,
+            intro,
+            stingers,
+            ending,
+            ending_fade: config
+                .ending_fade
+                .map(Duration::from_millis)
+                .unwrap_or(default_fade.duration),
+            parameters: config.parameters()?,
+            musics: musics_names
+                .iter()
+                .zip(musics_next_choices)
+                .zip(music_tables)
+                .map(|((name, next_choices), music_table)| {
                     Ok(Music {
                         name: name.clone(),
-                        loader: music_loader_factory(parsed_timing_music.music_index, name),
-                        next_choices: parsed_timing_music
-                            .choices
-                            .into_iter()
-                            .map(|parsed_choice| parsed_choice.into())
-                            .collect::<Vec<_>>(),
+                        title: music_table
+                            .as_ref()
+                            .and_then(|music_table| music_table.config.title.clone()),
+                        loader: music_loader_factory(name.clone()),
+                        next_choices: next_choices.unwrap_or_default(),
+                        loop_region: match &music_table {
+                            Some(music_table) => music_table.loop_region()?,
+                            None => None,
+                        },
+                        gain_db: music_table
+                            .as_ref()
+                            .and_then(|music_table| music_table.config.gain_db)
+                            .unwrap_or(0.0),
+                        ending: match &music_table {
+                            Some(music_table) => music_table
+                                .config
+                                .ending
+                                .as_ref()
+                                .map(|ending| clip(ending, &format!("{} ending", music_table.name)))
+                                .transpose()?,
+                            None => None,
+                        },
+                        shuffle: music_table
+                            .as_ref()
+                            .is_some_and(|music_table| music_table.config.shuffle),
                     })
                 })
-                .collect::<Result<Vec<Music<FeusicMusicLoader>>, Box<dyn Error>>>()?,
-        })
-    }
-}
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?,
+        };
 
-struct ParsedTiming<'a> {
-    first_music_index: usize,
-    timing_musics: ParsedTimingMusicIterator<'a>,
-}
+        let issues = feusic.validate_loaded(&duplicated_transitions);
+        if issues.iter().any(|i| i.severity == Severity::Error) {
+            return Err(InvalidFeusicError { issues }.into());
+        }
 
-struct ParsedTimingMusic {
-    music_index: usize,
-    choices: Vec<ParsedTimingChoice>,
+        Ok(feusic)
+    }
 }
 
-struct ParsedTimingChoice {
-    probability_weight: usize,
-    target_music_index: usize,
-    wait: (usize, usize),
+/// Names of the audio files among the files of a feusic.
+pub fn audio_files_names(files_names: &[String]) -> Vec<String> {
+    files_names
+        .iter()
+        .filter(|name| is_supported_audio_file(Path::new(name)))
+        .cloned()
+        .collect()
 }
 
-struct ParsedTimingMusicIterator<'a> {
-    chars: Peekable<Chars<'a>>,
-}
+/// Reads the feusic.toml of a `.feusic` zip file or folder together with the names of all
+/// the files in the feusic.
+pub fn read_feusic_toml(path: &Path) -> Result<(String, Vec<String>), Box<dyn Error>> {
+    let mut feusic_toml = String::new();
 
-impl<'a> ParsedTiming<'a> {
-    fn try_from(s: &'a str) -> Result<Self, Box<dyn Error>> {
-        let mut chars = s.chars().peekable();
+    if path.is_dir() {
+        File::open(path.join("feusic.toml"))
+            .map_err(|e| format!("feusic.toml should be in the .feusic folder. {}", e))?
+            .read_to_string(&mut feusic_toml)?;
 
-        match chars.next() {
-            Some('s') => {}
-            _ => return Err("Timing should start with 's'".into()),
-        }
+        Ok((feusic_toml, folder_files_names(path)?))
+    } else {
+        let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+        zip.by_name("feusic.toml")
+            .map_err(|e| format!("feusic.toml should be in a .feusic file. {}", e))?
+            .read_to_string(&mut feusic_toml)?;
 
-        let first_music_index = read_number(&mut chars)?;
+        let files_names = zip.file_names().map(|name| name.to_string()).collect();
 
-        Ok(Self {
-            first_music_index,
-            timing_musics: ParsedTimingMusicIterator { chars },
-        })
+        Ok((feusic_toml, files_names))
     }
 }
 
-impl<'a> Iterator for ParsedTimingMusicIterator<'a> {
-    type Item = Result<ParsedTimingMusic, Box<dyn Error>>;
+/// Names of the files of a `.feusic` folder, sorted.
+pub fn folder_files_names(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files_names = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+        .collect::<Vec<_>>();
+    files_names.sort();
+
+    Ok(files_names)
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut choices = vec![];
+/// Replaces the feusic.toml of a `.feusic` zip file or folder. Zip files are written next to
+/// the original and then moved over it, so a failure leaves the original untouched.
+pub fn write_feusic_toml(path: &Path, feusic_toml: &str) -> Result<(), Box<dyn Error>> {
+    if path.is_dir() {
+        fs::write(path.join("feusic.toml"), feusic_toml)?;
+        return Ok(());
+    }
 
-        let mut wait = None;
-        let mut probability_weight = None;
+    let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+    let temp_path = path.with_extension("feusic.tmp");
+    let mut writer = zip::ZipWriter::new(File::create(&temp_path)?);
 
-        match self.chars.next() {
-            Some('|') => {}
-            _ => return None,
+    for index in 0..zip.len() {
+        let file = zip.by_index_raw(index)?;
+        if file.name() != "feusic.toml" {
+            writer.raw_copy_file(file)?;
         }
+    }
 
-        let music_index = match read_number(&mut self.chars) {
-            Ok(number) => number,
-            Err(e) => return Some(Err(e)),
-        };
+    writer.start_file("feusic.toml", zip::write::SimpleFileOptions::default())?;
+    writer.write_all(feusic_toml.as_bytes())?;
+    writer.finish()?;
 
-        match self.chars.next() {
-            Some(':') => {}
-            _ => return Some(Err("Expected ':'".into())),
-        }
+    fs::rename(&temp_path, path)?;
 
-        let mut choices_with_probability = 0;
-        loop {
-            loop {
-                match self.chars.next() {
-                    Some('w') => {
-                        let wait_lower = match read_number(&mut self.chars) {
-                            Ok(number) => number,
-                            Err(e) => return Some(Err(e)),
-                        };
-                        let wait_higher = match self.chars.peek() {
-                            Some('-') => {
-                                self.chars.next();
-                                match read_number(&mut self.chars) {
-                                    Ok(number) => number,
-                                    Err(e) => return Some(Err(e)),
-                                }
-                            }
-                            _ => wait_lower,
-                        };
-                        wait = Some((wait_lower, wait_higher));
-                    }
-                    Some('p') => {
-                        choices_with_probability += 1;
-
-                        probability_weight = Some(match read_number(&mut self.chars) {
-                            Ok(number) => number,
-                            Err(e) => return Some(Err(e)),
-                        });
-                    }
-                    _ => return Some(Err("Expected either 'w' or 'p'".into())),
-                }
+    Ok(())
+}
 
-                match self.chars.peek() {
-                    Some(';') => {
-                        self.chars.next();
-                    }
-                    _ => break,
-                }
-            }
+impl<M> Feusic<M> {
+    pub fn is_layered(&self) -> bool {
+        !self.states.is_empty()
+    }
 
-            match self.chars.next() {
-                Some(':') => {}
-                _ => return Some(Err("Expected ':'".into())),
+    /// Transitions of every node of the graph the timer walks: the states in layered mode,
+    /// the musics otherwise.
+    pub fn transitions(&self) -> Vec<Vec<Next>> {
+        if self.is_layered() {
+            self.states.iter().map(|s| s.next_choices.clone()).collect()
+        } else {
+            self.musics.iter().map(|m| m.next_choices.clone()).collect()
+        }
+    }
+
+    /// Replaces the transitions of every node, see [`Feusic::transitions`].
+    pub fn set_transitions(&mut self, transitions: Vec<Vec<Next>>) {
+        if self.is_layered() {
+            for (state, next_choices) in self.states.iter_mut().zip(transitions) {
+                state.next_choices = next_choices;
             }
+        } else {
+            for (music, next_choices) in self.musics.iter_mut().zip(transitions) {
+                music.next_choices = next_choices;
+            }
+        }
+    }
 
-            let target_music_index = match read_number(&mut self.chars) {
-                Ok(number) => number,
-                Err(e) => return Some(Err(e)),
-            };
+    /// [`Feusic::transitions`] with their waits resolved to milliseconds, `musics_durations`
+    /// are the durations of the musics the loops are measured in when there is no loop region.
+    pub fn resolved_transitions(&self, musics_durations: &[Duration]) -> Vec<Vec<Next>> {
+        self.transitions()
+            .into_iter()
+            .enumerate()
+            .map(|(node, next_choices)| {
+                let loop_duration = self.loop_duration(node, musics_durations);
+                next_choices
+                    .into_iter()
+                    .map(|next| {
+                        let wait_lower = next.wait.0.resolve(self.tempo, loop_duration);
+                        let wait_higher = next.wait.1.resolve(self.tempo, loop_duration);
+                        Next {
+                            wait: (
+                                Wait::millis(wait_lower),
+                                Wait::millis(wait_higher.max(wait_lower)),
+                            ),
+                            ..next
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
-            let Some(wait) = wait else {
-                return Some(Err("no wait defined".into()));
-            };
+    /// Duration of a loop of `node`, measured on its music or on the first layer of the state
+    /// in layered mode.
+    fn loop_duration(&self, node: usize, musics_durations: &[Duration]) -> Duration {
+        let music_index = if self.is_layered() {
+            self.states
+                .get(node)
+                .and_then(|state| state.layers.iter().position(|layer| layer.is_some()))
+                .unwrap_or(0)
+        } else {
+            node
+        };
 
-            choices.push(ParsedTimingChoice {
-                probability_weight: probability_weight.unwrap_or(100),
-                target_music_index,
-                wait,
-            });
+        match self.loop_region(music_index) {
+            Some(region) => Duration::from_secs_f64((region.end - region.start).max(0.0)),
+            None => musics_durations
+                .get(music_index)
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
 
-            match self.chars.peek() {
-                Some('/') => {
-                    self.chars.next();
-                }
-                _ => break,
-            }
+    /// Loop region of a music, its own or else the one of the feusic.
+    fn loop_region(&self, music_index: usize) -> Option<LoopRegion> {
+        match (self.musics.get(music_index), &self.looping) {
+            (
+                Some(Music {
+                    loop_region: Some(region),
+                    ..
+                }),
+                _,
+            ) => Some(*region),
+            (_, Looping::Partial { start, end, .. }) => Some(LoopRegion {
+                start: *start,
+                end: *end,
+            }),
+            _ => None,
         }
+    }
 
-        if choices.len() > 1
-            && (choices.iter().any(|c| c.probability_weight == 0)
-                || choices.len() != choices_with_probability)
-        {
-            return Some(Err(
-                r#"When setting multiple choices they all need probability specified different than 0.
-                    e.g. s0|0:w120000-150000:1|1:w15000-60000;p30:2/w5000-10000;p70:0|2:w6000-20000:1"#.into(),
-            ));
+    /// Whether every node of the graph the timer walks draws its transitions from a shuffle
+    /// bag, see [`Feusic::transitions`].
+    pub fn shuffled_nodes(&self) -> Vec<bool> {
+        if self.is_layered() {
+            self.states.iter().map(|s| s.shuffle).collect()
+        } else {
+            self.musics.iter().map(|m| m.shuffle).collect()
+        }
+    }
+
+    /// Volume in dB of a music while `node`, a music or a state in layered mode, is playing.
+    /// `None` when the music is silent.
+    pub fn music_volume(&self, node: usize, music_index: usize) -> Option<f64> {
+        let music = self.musics.get(music_index)?;
+        if self.is_layered() {
+            let layer = (*self.states.get(node)?.layers.get(music_index)?)?;
+            Some(music.gain_db + layer)
+        } else {
+            (node == music_index).then_some(music.gain_db)
         }
+    }
 
-        Some(Ok(ParsedTimingMusic {
-            music_index,
-            choices,
-        }))
+    /// Whether the stinger of a transition from `node` to `next_node` ducks a music. Only the
+    /// musics fading out are ducked, the ones still audible after it keep their volume.
+    pub fn is_ducked_by_stinger(&self, node: usize, next_node: usize, music_index: usize) -> bool {
+        self.music_volume(node, music_index).is_some()
+            && self.music_volume(next_node, music_index).is_none()
     }
-}
 
-impl Into<Next> for ParsedTimingChoice {
-    fn into(self) -> Next {
-        Next {
-            probability_weight: self.probability_weight,
-            target_music: self.target_music_index,
-            wait: self.wait,
+    /// Ending to play when the duration expires while `node` is playing, the one of the music
+    /// if it has one. Layered feusics only use the feusic ending.
+    pub fn ending_of(&self, node: usize) -> Option<&Clip<M>> {
+        let music_ending = if self.is_layered() {
+            None
+        } else {
+            self.musics.get(node).and_then(|m| m.ending.as_ref())
+        };
+        music_ending.or(self.ending.as_ref())
+    }
+
+    /// Names of the nodes of the graph the timer walks, see [`Feusic::transitions`].
+    pub fn node_titles(&self) -> Vec<String> {
+        if self.is_layered() {
+            self.states.iter().map(|s| s.name.clone()).collect()
+        } else {
+            self.musics
+                .iter()
+                .map(|m| m.display_name().to_string())
+                .collect()
         }
     }
+
+    /// Title of the feusic, or its file name when it has none.
+    pub fn display_name(&self) -> &str {
+        self.meta.title.as_deref().unwrap_or(&self.name)
+    }
 }
 
-fn read_number(chars: &mut Peekable<Chars>) -> Result<usize, Box<dyn Error>> {
-    let mut n: usize = 0;
-    let mut found = false;
+impl<M> Music<M> {
+    /// Title of the music, or its file name when it has none.
+    pub fn display_name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+}
 
-    while let Some(maybe_number) = chars.peek() {
-        if let Some(number) = maybe_number.to_digit(10) {
-            found = true;
-            n = n * 10 + number as usize;
-            chars.next();
-        } else {
-            break;
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            duration: DEFAULT_FADE_DURATION,
+            easing: FadeEasing::default(),
         }
     }
+}
 
-    if found {
-        Ok(n)
-    } else {
-        Err("Expected number".into())
+impl Default for FadeEasing {
+    fn default() -> Self {
+        FadeEasing::Power(0.15)
     }
 }
 
@@ -414,56 +727,171 @@ impl Looping {
 
 #[cfg(test)]
 mod test {
+    use test_utils::{load, reload, NUMBERED_MUSICS};
+
     use super::*;
 
     #[test]
-    fn parse_complex_timing() {
-        let timing = "s0|0:w120000-180000;p8:1/w2000-5000;p2:4|1:w10000-20000;p1:2/w10000-20000;p1:3/w10000-20000;p1:1|2:w5000-10000:1|3:w5000-10000:1|4:w120000-180000:1";
-        let parsed_timing = ParsedTiming::try_from(timing).unwrap();
-
-        let timing_musics: Vec<_> = parsed_timing
-            .timing_musics
-            .collect::<Result<_, _>>()
-            .unwrap();
-        assert_eq!(timing_musics.len(), 5);
-
-        assert_eq!(timing_musics[0].music_index, 0);
-        assert_eq!(timing_musics[0].choices.len(), 2);
-        assert_eq!(timing_musics[0].choices[0].probability_weight, 8);
-        assert_eq!(timing_musics[0].choices[0].target_music_index, 1);
-        assert_eq!(timing_musics[0].choices[0].wait, (120000, 180000));
-        assert_eq!(timing_musics[0].choices[1].probability_weight, 2);
-        assert_eq!(timing_musics[0].choices[1].target_music_index, 4);
-        assert_eq!(timing_musics[0].choices[1].wait, (2000, 5000));
-
-        assert_eq!(timing_musics[1].music_index, 1);
-        assert_eq!(timing_musics[1].choices.len(), 3);
-        assert_eq!(timing_musics[1].choices[0].probability_weight, 1);
-        assert_eq!(timing_musics[1].choices[0].target_music_index, 2);
-        assert_eq!(timing_musics[1].choices[0].wait, (10000, 20000));
-        assert_eq!(timing_musics[1].choices[1].probability_weight, 1);
-        assert_eq!(timing_musics[1].choices[1].target_music_index, 3);
-        assert_eq!(timing_musics[1].choices[1].wait, (10000, 20000));
-        assert_eq!(timing_musics[1].choices[2].probability_weight, 1);
-        assert_eq!(timing_musics[1].choices[2].target_music_index, 1);
-        assert_eq!(timing_musics[1].choices[2].wait, (10000, 20000));
-
-        assert_eq!(timing_musics[2].music_index, 2);
-        assert_eq!(timing_musics[2].choices.len(), 1);
-        assert_eq!(timing_musics[2].choices[0].probability_weight, 100);
-        assert_eq!(timing_musics[2].choices[0].target_music_index, 1);
-        assert_eq!(timing_musics[2].choices[0].wait, (5000, 10000));
-
-        assert_eq!(timing_musics[3].music_index, 3);
-        assert_eq!(timing_musics[3].choices.len(), 1);
-        assert_eq!(timing_musics[3].choices[0].probability_weight, 100);
-        assert_eq!(timing_musics[3].choices[0].target_music_index, 1);
-        assert_eq!(timing_musics[3].choices[0].wait, (5000, 10000));
-
-        assert_eq!(timing_musics[4].music_index, 4);
-        assert_eq!(timing_musics[4].choices.len(), 1);
-        assert_eq!(timing_musics[4].choices[0].probability_weight, 100);
-        assert_eq!(timing_musics[4].choices[0].target_music_index, 1);
-        assert_eq!(timing_musics[4].choices[0].wait, (120000, 180000));
+    fn musics_can_override_the_loop_region() {
+        let feusic = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                timing = "s0|0:w5000:1|1:w5000:0"
+                duration = 600
+                loop_start = 2.5
+                loop_end = 114.5
+
+                [[music]]
+                index = 1
+                loop_start = 10.0
+                loop_end = 90.25
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(feusic.musics[0].loop_region, None);
+        assert_eq!(
+            feusic.musics[1].loop_region,
+            Some(LoopRegion {
+                start: 10.0,
+                end: 90.25
+            })
+        );
+        assert!(feusic
+            .to_feusic_toml()
+            .unwrap()
+            .contains("loop_end = 90.25"));
+    }
+
+    #[test]
+    fn zip_and_folder_feusics_have_the_same_musics() {
+        let dir = std::env::temp_dir().join(format!("feusic-test-{}", std::process::id()));
+        let folder_path = dir.join("folder.feusic");
+        let zip_path = dir.join("zip.feusic");
+        fs::create_dir_all(&folder_path).unwrap();
+
+        let feusic_toml = "timing = \"s0|0:w1000:1|1:w1000:2|2:w1000:0\"\nduration = 600";
+        let files = [
+            ("c.flac", ""),
+            ("feusic.toml", feusic_toml),
+            ("notes.txt", ""),
+            ("a.mp3", ""),
+            ("b.OGG", ""),
+        ];
+
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        for (name, content) in files {
+            fs::write(folder_path.join(name), content).unwrap();
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut zip, content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let from_folder = Feusic::from_feusic_folder(&folder_path).unwrap();
+        let from_zip =
+            Feusic::from_feusic_zip_file(&zip_path, &File::open(&zip_path).unwrap()).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names = |feusic: &Feusic<FeusicMusicLoader>| {
+            feusic
+                .musics
+                .iter()
+                .map(|m| m.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&from_folder), vec!["a.mp3", "b.OGG", "c.flac"]);
+        assert_eq!(names(&from_zip), names(&from_folder));
+        assert_eq!(from_zip.start, from_folder.start);
+        for (zip_music, folder_music) in from_zip.musics.iter().zip(&from_folder.musics) {
+            assert_eq!(zip_music.next_choices, folder_music.next_choices);
+            assert_eq!(zip_music.loop_region, folder_music.loop_region);
+            assert!(matches!(
+                &zip_music.loader,
+                FeusicMusicLoader::ZipFeusic { feusic_path, music_name }
+                    if *feusic_path == zip_path.to_str().unwrap() && *music_name == zip_music.name
+            ));
+        }
+    }
+
+    #[test]
+    fn musics_list_defines_the_order() {
+        let files_names = ["a.mp3", "b.mp3", "c.mp3"];
+
+        let feusic = load(
+            &files_names,
+            r#"
+                musics = ["c.mp3", "a.mp3"]
+                timing = "s0|0:w1000:1|1:w1000:0"
+                duration = 600
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            feusic
+                .musics
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["c.mp3", "a.mp3"]
+        );
+
+        let error = load(
+            &files_names,
+            r#"
+                musics = ["a.mp3", "d.mp3"]
+                timing = "s0|0:w1000:1|1:w1000:0"
+                duration = 600
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "`musics`: d.mp3 is not in the feusic");
+    }
+
+    #[test]
+    fn meta_and_music_titles_are_read_and_serialized() {
+        let files_names = ["0.mp3", "1.mp3", "cover.png"];
+        let feusic_toml = r#"
+            timing = "s0|0:w1000:1|1:w1000:0"
+            duration = 600
+
+            [meta]
+            title = "Fire Emblem Engage - Map"
+            game = "Fire Emblem Engage"
+            year = 2023
+            tags = ["strategy", "map"]
+            cover = "cover.png"
+
+            [[music]]
+            index = 1
+            title = "Battle"
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        assert_eq!(feusic.display_name(), "Fire Emblem Engage - Map");
+        assert_eq!(feusic.meta.year, Some(2023));
+        assert_eq!(feusic.meta.tags, vec!["strategy", "map"]);
+        assert!(matches!(
+            &feusic.cover,
+            Some(FeusicMusicLoader::FolderFeusic { music_path }) if music_path == "cover.png"
+        ));
+        assert_eq!(feusic.musics[0].display_name(), "0.mp3");
+        assert_eq!(feusic.musics[1].display_name(), "Battle");
+
+        let from_feusic_toml = reload(&feusic, &files_names);
+
+        assert_eq!(from_feusic_toml.meta, feusic.meta);
+        assert_eq!(from_feusic_toml.musics[1].title, feusic.musics[1].title);
+
+        let error = load(&files_names[..2], feusic_toml).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[meta] cover: cover.png is not in the feusic"
+        );
     }
 }
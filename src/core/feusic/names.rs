@@ -0,0 +1,277 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Display,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Reference to a music in feusic.toml, either its index or its name.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(super) enum MusicRef {
+    Index(usize),
+    Name(String),
+}
+
+/// Names that can be used to reference the musics of a feusic: the aliases declared in
+/// feusic.toml, the file stems and the file names, in this order of precedence.
+#[derive(Debug, Default)]
+pub(super) struct MusicNames {
+    musics_count: usize,
+    aliases: HashMap<String, usize>,
+    stems: HashMap<String, Vec<usize>>,
+    file_names: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MusicNameError {
+    /// No alias, file stem or file name matches the name.
+    Unknown(String),
+    /// The name is the stem of more than one file, the file names are listed.
+    Ambiguous(String, Vec<String>),
+}
+
+impl MusicNames {
+    pub fn new(
+        musics_names: &[String],
+        aliases: &BTreeMap<String, String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut names = Self {
+            musics_count: musics_names.len(),
+            ..Default::default()
+        };
+
+        for (music_index, music_name) in musics_names.iter().enumerate() {
+            names.file_names.insert(music_name.clone(), music_index);
+            names
+                .stems
+                .entry(file_stem(music_name))
+                .or_default()
+                .push(music_index);
+        }
+
+        for (alias, music_name) in aliases.iter() {
+            let music_index = names
+                .resolve_file(music_name)
+                .map_err(|e| format!("[aliases] {}: {}", alias, e))?;
+            names.aliases.insert(alias.clone(), music_index);
+        }
+
+        Ok(names)
+    }
+
+    pub fn musics_count(&self) -> usize {
+        self.musics_count
+    }
+
+    /// Index of the music with the given alias, file stem or file name.
+    pub fn resolve(&self, name: &str) -> Result<usize, MusicNameError> {
+        match self.aliases.get(name) {
+            Some(music_index) => Ok(*music_index),
+            None => self.resolve_file(name),
+        }
+    }
+
+    /// Index of the music referenced by a [`MusicRef`], indices are not checked to exist.
+    pub fn resolve_ref(&self, music_ref: &MusicRef) -> Result<usize, MusicNameError> {
+        match music_ref {
+            MusicRef::Index(music_index) => Ok(*music_index),
+            MusicRef::Name(name) => self.resolve(name),
+        }
+    }
+
+    fn resolve_file(&self, name: &str) -> Result<usize, MusicNameError> {
+        match self.stems.get(name).map(|indices| indices.as_slice()) {
+            Some([music_index]) => Ok(*music_index),
+            Some(indices) => {
+                let mut file_names = self
+                    .file_names
+                    .iter()
+                    .filter(|(_, index)| indices.contains(index))
+                    .map(|(file_name, _)| file_name.clone())
+                    .collect::<Vec<_>>();
+                file_names.sort();
+                Err(MusicNameError::Ambiguous(name.to_string(), file_names))
+            }
+            None => self
+                .file_names
+                .get(name)
+                .copied()
+                .ok_or_else(|| MusicNameError::Unknown(name.to_string())),
+        }
+    }
+}
+
+fn file_stem(music_name: &str) -> String {
+    Path::new(music_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(music_name)
+        .to_string()
+}
+
+impl Display for MusicRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MusicRef::Index(music_index) => write!(f, "index {}", music_index),
+            MusicRef::Name(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+impl Display for MusicNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MusicNameError::Unknown(name) => write!(f, "no music named @{}", name),
+            MusicNameError::Ambiguous(name, file_names) => write!(
+                f,
+                "@{} matches more than one music ({}), use the file name or an alias",
+                name,
+                file_names.join(", ")
+            ),
+        }
+    }
+}
+
+impl Error for MusicNameError {}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::test_utils::load;
+
+    use super::*;
+
+    #[test]
+    fn resolves_aliases_then_stems_then_file_names() {
+        let musics_names = vec![
+            "map.mp3".to_string(),
+            "battle.mp3".to_string(),
+            "battle.ogg".to_string(),
+        ];
+        let aliases = BTreeMap::from([("fight".to_string(), "battle.ogg".to_string())]);
+        let names = MusicNames::new(&musics_names, &aliases).unwrap();
+
+        assert_eq!(names.resolve("map"), Ok(0));
+        assert_eq!(names.resolve("battle.mp3"), Ok(1));
+        assert_eq!(names.resolve("fight"), Ok(2));
+        assert_eq!(
+            names.resolve("battle"),
+            Err(MusicNameError::Ambiguous(
+                "battle".to_string(),
+                vec!["battle.mp3".to_string(), "battle.ogg".to_string()]
+            ))
+        );
+        assert_eq!(
+            names.resolve("boss"),
+            Err(MusicNameError::Unknown("boss".to_string()))
+        );
+    }
+
+    #[test]
+    fn aliases_need_to_point_at_a_music() {
+        let musics_names = vec!["map.mp3".to_string()];
+        let aliases = BTreeMap::from([("boss".to_string(), "boss.mp3".to_string())]);
+
+        assert_eq!(
+            MusicNames::new(&musics_names, &aliases)
+                .unwrap_err()
+                .to_string(),
+            "[aliases] boss: no music named @boss.mp3"
+        );
+    }
+
+    #[test]
+    fn musics_can_be_referenced_by_name() {
+        let musics_names = ["01 - map.mp3", "02 - battle.mp3", "03 - boss.mp3"];
+
+        let from_indices = load(
+            &musics_names,
+            r#"
+                timing = "s0|0:w60000:1|1:w5000;p80:0/w5000;p20:2|2:w9000:0"
+                duration = 600
+            "#,
+        )
+        .unwrap();
+
+        let from_string = load(
+            &musics_names,
+            r#"
+                timing = "s@map|@map:w60000:@02 - battle|@02 - battle:w5000;p80:@map/w5000;p20:@boss|2:w9000:@01 - map.mp3"
+                duration = 600
+
+                [aliases]
+                map = "01 - map"
+                boss = "03 - boss.mp3"
+            "#,
+        )
+        .unwrap();
+
+        let from_tables = load(
+            &musics_names,
+            r#"
+                start = "map"
+                duration = 600
+
+                [aliases]
+                map = "01 - map"
+                battle = "02 - battle"
+
+                [[music]]
+                index = "map"
+                [[music.next]]
+                target = "battle"
+                wait_min = 60000
+
+                [[music]]
+                index = "battle"
+                [[music.next]]
+                target = 0
+                wait_min = 5000
+                weight = 80
+                [[music.next]]
+                target = "03 - boss"
+                wait_min = 5000
+                weight = 20
+
+                [[music]]
+                index = 2
+                [[music.next]]
+                target = "map"
+                wait_min = 9000
+            "#,
+        )
+        .unwrap();
+
+        for feusic in [&from_string, &from_tables] {
+            assert_eq!(feusic.start, from_indices.start);
+            for (music, indices_music) in feusic.musics.iter().zip(&from_indices.musics) {
+                assert_eq!(music.next_choices, indices_music.next_choices);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_music_names_fail_to_load() {
+        let error = load(
+            &["map.mp3"],
+            r#"
+                start = "map"
+                duration = 600
+
+                [[music]]
+                index = "map"
+                [[music.next]]
+                target = "boss"
+                wait_min = 5000
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (@map) [[music.next]] #1: no music named @boss"
+        );
+    }
+}
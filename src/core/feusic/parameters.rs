@@ -0,0 +1,282 @@
+use std::fmt::Display;
+
+use super::Next;
+
+/// Value set while the feusic plays, e.g. by a game, that the transitions can depend on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+/// Comparisons of parameters with values that all need to hold for a transition to happen,
+/// e.g. `intensity >= 0.7 && danger < 0.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub comparisons: Vec<Comparison>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// Index of the parameter in [`super::Feusic::parameters`].
+    pub parameter: usize,
+    pub operator: Operator,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// Longer operators first so `>=` is not read as `>`.
+const OPERATORS: [(&str, Operator); 6] = [
+    (">=", Operator::GreaterOrEqual),
+    ("<=", Operator::LessOrEqual),
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    (">", Operator::Greater),
+    ("<", Operator::Less),
+];
+
+impl Condition {
+    pub fn parse(condition: &str, parameters: &[Parameter]) -> Result<Self, String> {
+        let comparisons = condition
+            .split("&&")
+            .map(|comparison| Comparison::parse(comparison.trim(), parameters))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { comparisons })
+    }
+
+    /// Whether the condition holds for the `values` of the parameters.
+    pub fn holds(&self, values: &[f64]) -> bool {
+        self.comparisons
+            .iter()
+            .all(|comparison| comparison.holds(values))
+    }
+
+    /// The condition as written in feusic.toml.
+    pub fn describe(&self, parameters: &[Parameter]) -> String {
+        self.comparisons
+            .iter()
+            .map(|comparison| {
+                let name = parameters
+                    .get(comparison.parameter)
+                    .map(|parameter| parameter.name.as_str())
+                    .unwrap_or("?");
+                format!("{} {} {}", name, comparison.operator, comparison.value)
+            })
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+impl Comparison {
+    fn parse(comparison: &str, parameters: &[Parameter]) -> Result<Self, String> {
+        let (position, symbol, operator) = OPERATORS
+            .iter()
+            .filter_map(|(symbol, operator)| {
+                comparison
+                    .find(symbol)
+                    .map(|position| (position, *symbol, *operator))
+            })
+            .min_by_key(|(position, _, _)| *position)
+            .ok_or_else(|| {
+                format!(
+                    "expected a comparison like `intensity >= 0.7`, found `{}`",
+                    comparison
+                )
+            })?;
+
+        let name = comparison[..position].trim();
+        let value = comparison[position + symbol.len()..].trim();
+
+        Ok(Self {
+            parameter: parameters
+                .iter()
+                .position(|parameter| parameter.name == name)
+                .ok_or_else(|| format!("no parameter named {}", name))?,
+            operator,
+            value: value
+                .parse()
+                .map_err(|_| format!("`{}` is not a number", value))?,
+        })
+    }
+
+    fn holds(&self, values: &[f64]) -> bool {
+        let Some(value) = values.get(self.parameter) else {
+            return false;
+        };
+
+        match self.operator {
+            Operator::Less => *value < self.value,
+            Operator::LessOrEqual => *value <= self.value,
+            Operator::Greater => *value > self.value,
+            Operator::GreaterOrEqual => *value >= self.value,
+            Operator::Equal => *value == self.value,
+            Operator::NotEqual => *value != self.value,
+        }
+    }
+}
+
+impl Next {
+    /// Whether the transition can happen with the `values` of the parameters.
+    pub fn is_available(&self, values: &[f64]) -> bool {
+        self.when
+            .as_ref()
+            .is_none_or(|condition| condition.holds(values))
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = OPERATORS
+            .iter()
+            .find(|(_, operator)| operator == self)
+            .map(|(symbol, _)| *symbol)
+            .unwrap_or("?");
+        write!(f, "{}", symbol)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::test_utils::{load, reload};
+
+    use super::*;
+
+    fn parameters() -> Vec<Parameter> {
+        ["danger", "intensity"]
+            .into_iter()
+            .map(|name| Parameter {
+                name: name.to_string(),
+                min: 0.0,
+                max: 1.0,
+                default: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conditions_hold_when_all_their_comparisons_do() {
+        let condition = Condition::parse("intensity >= 0.7 && danger<0.5", &parameters()).unwrap();
+
+        assert_eq!(
+            condition.comparisons,
+            vec![
+                Comparison {
+                    parameter: 1,
+                    operator: Operator::GreaterOrEqual,
+                    value: 0.7
+                },
+                Comparison {
+                    parameter: 0,
+                    operator: Operator::Less,
+                    value: 0.5
+                },
+            ]
+        );
+        assert!(condition.holds(&[0.2, 0.7]));
+        assert!(!condition.holds(&[0.5, 0.7]));
+        assert!(!condition.holds(&[0.2, 0.6]));
+        assert_eq!(
+            condition.describe(&parameters()),
+            "intensity >= 0.7 && danger < 0.5"
+        );
+    }
+
+    #[test]
+    fn invalid_conditions_are_explained() {
+        assert_eq!(
+            Condition::parse("stealth > 0.5", &parameters()).unwrap_err(),
+            "no parameter named stealth"
+        );
+        assert_eq!(
+            Condition::parse("danger > high", &parameters()).unwrap_err(),
+            "`high` is not a number"
+        );
+        assert_eq!(
+            Condition::parse("danger", &parameters()).unwrap_err(),
+            "expected a comparison like `intensity >= 0.7`, found `danger`"
+        );
+    }
+
+    #[test]
+    fn transitions_can_depend_on_parameters() {
+        let files_names = ["map.mp3", "battle.mp3"];
+        let feusic_toml = r#"
+            start = "map"
+            duration = 600
+
+            [parameters]
+            danger = { default = 0.2 }
+            intensity = { min = 0.0, max = 10.0 }
+
+            [[music]]
+            index = "map"
+
+            [[music.next]]
+            target = "battle"
+            wait_min = 0
+            when = "danger >= 0.7"
+
+            [[music]]
+            index = "battle"
+
+            [[music.next]]
+            target = "map"
+            wait_min = 1000
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        assert_eq!(
+            feusic.parameters,
+            vec![
+                Parameter {
+                    name: "danger".to_string(),
+                    min: 0.0,
+                    max: 1.0,
+                    default: 0.2,
+                },
+                Parameter {
+                    name: "intensity".to_string(),
+                    min: 0.0,
+                    max: 10.0,
+                    default: 0.0,
+                },
+            ]
+        );
+        let to_battle = &feusic.musics[1].next_choices[0];
+        assert!(!to_battle.is_available(&[0.2, 0.0]));
+        assert!(to_battle.is_available(&[0.7, 0.0]));
+        assert!(feusic.musics[0].next_choices[0].is_available(&[0.2, 0.0]));
+
+        let from_feusic_toml = reload(&feusic, &files_names);
+
+        assert_eq!(from_feusic_toml.parameters, feusic.parameters);
+        assert_eq!(
+            from_feusic_toml.musics[1].next_choices,
+            feusic.musics[1].next_choices
+        );
+
+        let error = load(
+            &files_names,
+            &feusic_toml.replace("danger >=", "stealth >="),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (@map) [[music.next]] #1: when: no parameter named stealth"
+        );
+    }
+}
@@ -0,0 +1,501 @@
+use std::{collections::HashMap, time::Duration};
+
+use rand::Rng;
+
+use super::{Feusic, Next};
+
+/// Limits on how often a transition can be chosen. They come from the `[[music.next]]` or
+/// `[[state.next]]` table of the transition, or else from the table of its target.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Constraints {
+    /// Minimum time since the target was last entered.
+    pub cooldown: Option<Duration>,
+    /// Minimum number of transitions since the target was last entered.
+    pub cooldown_transitions: Option<usize>,
+    /// Maximum number of times in a row the transition is chosen when leaving its music.
+    pub max_consecutive: Option<usize>,
+}
+
+impl Constraints {
+    /// Constraints with the ones of `self` that are not defined taken from `other`.
+    pub fn or(self, other: Constraints) -> Constraints {
+        Constraints {
+            cooldown: self.cooldown.or(other.cooldown),
+            cooldown_transitions: self.cooldown_transitions.or(other.cooldown_transitions),
+            max_consecutive: self.max_consecutive.or(other.max_consecutive),
+        }
+    }
+}
+
+impl<M> Feusic<M> {
+    /// Chooses the music, or state in layered mode, to start from at random by the probability
+    /// weights of [`Feusic::start`].
+    pub fn choose_start(&self, rng: &mut impl Rng) -> usize {
+        let probability_total = self
+            .start
+            .iter()
+            .map(|start| start.probability_weight)
+            .sum();
+        if probability_total == 0 {
+            return self.start.first().map(|start| start.node).unwrap_or(0);
+        }
+        let mut random_probability = rng.random_range(0..probability_total);
+
+        self.start
+            .iter()
+            .find(|start| {
+                if random_probability < start.probability_weight {
+                    true
+                } else {
+                    random_probability -= start.probability_weight;
+                    false
+                }
+            })
+            .map(|start| start.node)
+            .unwrap_or(0)
+    }
+}
+
+/// Transitions taken so far in a feusic, used to choose the next ones so that they respect
+/// their [`Constraints`] and the shuffle bags of the musics, or states in layered mode.
+///
+/// Constraints are soft: when none of the available transitions respects them, they are
+/// ignored rather than leaving the music stuck.
+#[derive(Debug, Default)]
+pub struct TransitionHistory {
+    /// Nodes that choose their transitions from a shuffle bag.
+    shuffled: Vec<bool>,
+    transitions_count: usize,
+    /// When each node was last entered, and after how many transitions.
+    last_entered: HashMap<usize, (Duration, usize)>,
+    /// Last transition chosen when leaving each node and how many times in a row.
+    consecutive: HashMap<usize, (usize, usize)>,
+    /// Transitions of each shuffled node already chosen in the current round.
+    bags: HashMap<usize, Vec<usize>>,
+}
+
+impl TransitionHistory {
+    /// `shuffled` tells for every node whether its transitions are drawn from a shuffle bag,
+    /// every target is visited once before any of them repeats.
+    pub fn new(shuffled: Vec<bool>) -> Self {
+        Self {
+            shuffled,
+            ..Default::default()
+        }
+    }
+
+    /// Records that `node` is playing without a transition, like the first one.
+    pub fn enter(&mut self, node: usize, now: Duration) {
+        self.last_entered
+            .insert(node, (now, self.transitions_count));
+    }
+
+    /// Chooses the transition to take from `node`, at random by the probability weights of
+    /// the ones that are available with the `parameters` and respect the history.
+    /// `now` is the time since the feusic started.
+    pub fn choose(
+        &self,
+        node: usize,
+        choices: &[Next],
+        parameters: &[f64],
+        now: Duration,
+        rng: &mut impl Rng,
+    ) -> Option<usize> {
+        let available = (0..choices.len())
+            .filter(|case_index| choices[*case_index].is_available(parameters))
+            .collect::<Vec<_>>();
+
+        let mut candidates = available
+            .iter()
+            .copied()
+            .filter(|case_index| {
+                self.respects_constraints(node, *case_index, &choices[*case_index], now)
+            })
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            candidates = available;
+        }
+
+        if self.is_shuffled(node) {
+            let bag = self.bags.get(&node);
+            let undrawn = candidates
+                .iter()
+                .copied()
+                .filter(|case_index| !bag.is_some_and(|bag| bag.contains(case_index)))
+                .collect::<Vec<_>>();
+            if !undrawn.is_empty() {
+                candidates = undrawn;
+            }
+        }
+
+        let probability_total = candidates
+            .iter()
+            .map(|case_index| choices[*case_index].probability_weight)
+            .sum();
+        if probability_total == 0 {
+            return None;
+        }
+        let mut random_probability = rng.random_range(0..probability_total);
+
+        candidates.into_iter().find(|case_index| {
+            let weight = choices[*case_index].probability_weight;
+            if random_probability < weight {
+                true
+            } else {
+                random_probability -= weight;
+                false
+            }
+        })
+    }
+
+    /// Chooses the transition to take from `node` like [`TransitionHistory::choose`], with
+    /// the time to wait before taking it drawn between its waits, which are expected to be
+    /// resolved to milliseconds, see [`Feusic::resolved_transitions`].
+    pub fn choose_with_wait(
+        &self,
+        node: usize,
+        choices: &[Next],
+        parameters: &[f64],
+        now: Duration,
+        rng: &mut impl Rng,
+    ) -> Option<(usize, Duration)> {
+        let case_index = self.choose(node, choices, parameters, now, rng)?;
+        let wait = choices[case_index].wait;
+        let time_to_wait = rng.random_range(wait.0.amount..=wait.1.amount);
+
+        Some((case_index, Duration::from_millis(time_to_wait as u64)))
+    }
+
+    /// Records the transition `case_index` of `node` to `target`.
+    pub fn record(&mut self, node: usize, case_index: usize, target: usize, now: Duration) {
+        self.transitions_count += 1;
+        self.last_entered
+            .insert(target, (now, self.transitions_count));
+
+        let consecutive = self.consecutive.entry(node).or_insert((case_index, 0));
+        if consecutive.0 != case_index {
+            *consecutive = (case_index, 0);
+        }
+        consecutive.1 += 1;
+
+        if self.is_shuffled(node) {
+            let bag = self.bags.entry(node).or_default();
+            // A transition already in the bag was chosen because the round is over.
+            if bag.contains(&case_index) {
+                bag.clear();
+            }
+            bag.push(case_index);
+        }
+    }
+
+    fn is_shuffled(&self, node: usize) -> bool {
+        self.shuffled.get(node).copied().unwrap_or(false)
+    }
+
+    fn respects_constraints(
+        &self,
+        node: usize,
+        case_index: usize,
+        next: &Next,
+        now: Duration,
+    ) -> bool {
+        let constraints = next.constraints;
+
+        if let Some((entered_at, entered_after)) = self.last_entered.get(&next.target_music) {
+            if constraints
+                .cooldown
+                .is_some_and(|cooldown| now.saturating_sub(*entered_at) < cooldown)
+            {
+                return false;
+            }
+            if constraints
+                .cooldown_transitions
+                .is_some_and(|cooldown| self.transitions_count - entered_after < cooldown)
+            {
+                return false;
+            }
+        }
+
+        match (constraints.max_consecutive, self.consecutive.get(&node)) {
+            (Some(max_consecutive), Some((last_case_index, count))) => {
+                *last_case_index != case_index || *count < max_consecutive
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::core::feusic::{
+        test_utils::{self, load},
+        Start,
+    };
+
+    use super::*;
+
+    fn next(target_music: usize, constraints: Constraints) -> Next {
+        Next {
+            constraints,
+            ..test_utils::next(target_music, (1000, 1000))
+        }
+    }
+
+    /// Takes `count` transitions from node 0, which always comes back to itself.
+    fn choose_from_node_0(
+        history: &mut TransitionHistory,
+        choices: &[Next],
+        count: usize,
+    ) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..count)
+            .map(|transition| {
+                let now = Duration::from_secs(transition as u64);
+                let case_index = history.choose(0, choices, &[], now, &mut rng).unwrap();
+                history.record(0, case_index, choices[case_index].target_music, now);
+                history.enter(0, now);
+                choices[case_index].target_music
+            })
+            .collect()
+    }
+
+    #[test]
+    fn max_consecutive_limits_repeats() {
+        let choices = vec![
+            next(
+                1,
+                Constraints {
+                    max_consecutive: Some(1),
+                    ..Default::default()
+                },
+            ),
+            next(
+                2,
+                Constraints {
+                    max_consecutive: Some(1),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let mut history = TransitionHistory::new(vec![false; 3]);
+
+        let targets = choose_from_node_0(&mut history, &choices, 10);
+
+        assert!(targets.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn cooldowns_wait_for_time_and_transitions() {
+        let constraints = Constraints {
+            cooldown: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut history = TransitionHistory::new(vec![false; 3]);
+        history.record(0, 0, 1, Duration::from_secs(10));
+        let mut rng = StdRng::seed_from_u64(0);
+        let choices = vec![next(1, constraints), next(2, Constraints::default())];
+
+        assert_eq!(
+            history.choose(0, &choices, &[], Duration::from_secs(30), &mut rng),
+            Some(1)
+        );
+        assert!(history.respects_constraints(0, 0, &choices[0], Duration::from_secs(70)));
+
+        let constraints = Constraints {
+            cooldown_transitions: Some(1),
+            ..Default::default()
+        };
+        assert!(!history.respects_constraints(0, 0, &next(1, constraints), Duration::ZERO));
+        history.record(1, 0, 0, Duration::ZERO);
+        assert!(history.respects_constraints(0, 0, &next(1, constraints), Duration::ZERO));
+    }
+
+    #[test]
+    fn constraints_are_ignored_when_nothing_respects_them() {
+        let constraints = Constraints {
+            cooldown_transitions: Some(10),
+            ..Default::default()
+        };
+        let mut history = TransitionHistory::new(vec![false; 2]);
+        history.record(0, 0, 1, Duration::ZERO);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            history.choose(0, &[next(1, constraints)], &[], Duration::ZERO, &mut rng),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn shuffle_bags_visit_every_target_before_repeating() {
+        let choices = (1..=4)
+            .map(|target| next(target, Constraints::default()))
+            .collect::<Vec<_>>();
+        let mut history = TransitionHistory::new(vec![true, false, false, false, false]);
+
+        let targets = choose_from_node_0(&mut history, &choices, 12);
+
+        for round in targets.chunks(4) {
+            let mut round = round.to_vec();
+            round.sort();
+            assert_eq!(round, vec![1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn transitions_can_limit_repeats() {
+        let files_names = ["map.mp3", "town.mp3", "cave.mp3"];
+        let feusic_toml = r#"
+            start = "map"
+            duration = 600
+
+            [[music]]
+            index = "map"
+            shuffle = true
+
+            [[music.next]]
+            target = "town"
+            wait_min = 1000
+            weight = 50
+
+            [[music.next]]
+            target = "cave"
+            wait_min = 1000
+            weight = 50
+            max_consecutive = 2
+
+            [[music]]
+            index = "town"
+            cooldown = 60000
+
+            [[music.next]]
+            target = "map"
+            wait_min = 1000
+
+            [[music]]
+            index = "cave"
+            cooldown_transitions = 3
+
+            [[music.next]]
+            target = "map"
+            wait_min = 1000
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        // Musics are sorted by name: cave, map, town.
+        let constraints = feusic.musics[1]
+            .next_choices
+            .iter()
+            .map(|next| next.constraints)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            constraints,
+            vec![
+                Constraints {
+                    cooldown: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                },
+                Constraints {
+                    cooldown_transitions: Some(3),
+                    max_consecutive: Some(2),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(feusic.shuffled_nodes(), vec![false, true, false]);
+
+        let from_feusic_toml = test_utils::reload(&feusic, &files_names);
+
+        assert_eq!(from_feusic_toml.shuffled_nodes(), feusic.shuffled_nodes());
+        assert_eq!(
+            from_feusic_toml.musics[1].next_choices,
+            feusic.musics[1].next_choices
+        );
+
+        let error = load(
+            &files_names,
+            &feusic_toml.replace("max_consecutive = 2", "max_consecutive = 0"),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (@map) [[music.next]] #2: max_consecutive should be greater than 0"
+        );
+    }
+
+    #[test]
+    fn feusics_can_start_from_weighted_musics() {
+        let files_names = ["map.mp3", "town.mp3"];
+
+        let from_string = load(
+            &files_names,
+            r#"
+            timing = "s@town;p30/@map;p70|0:w5000:1|1:w5000:0"
+            duration = 600
+            "#,
+        )
+        .unwrap();
+        let from_tables = load(
+            &files_names,
+            r#"
+            start = { map = 70, town = 30 }
+            duration = 600
+
+            [[music]]
+            index = "map"
+            next = [{ target = "town", wait_min = 5000 }]
+
+            [[music]]
+            index = "town"
+            next = [{ target = "map", wait_min = 5000 }]
+            "#,
+        )
+        .unwrap();
+
+        let mut start = from_string.start.clone();
+        start.sort_by_key(|start| start.node);
+        assert_eq!(
+            start,
+            vec![
+                Start {
+                    node: 0,
+                    probability_weight: 70
+                },
+                Start {
+                    node: 1,
+                    probability_weight: 30
+                },
+            ]
+        );
+        assert_eq!(from_tables.start, start);
+        assert_eq!(
+            from_tables.to_timing_string().unwrap(),
+            "s0;p70/1;p30|0:w5000:1|1:w5000:0"
+        );
+        assert_eq!(test_utils::reload(&from_tables, &files_names).start, start);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let starts = (0..100)
+            .map(|_| from_tables.choose_start(&mut rng))
+            .collect::<Vec<_>>();
+        assert!(starts.contains(&0) && starts.contains(&1));
+
+        let error = load(
+            &files_names,
+            r#"
+            start = { map = 70, cave = 30 }
+            duration = 600
+
+            [[music]]
+            index = "map"
+            next = [{ target = "town", wait_min = 5000 }]
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "`start`: no music named @cave");
+    }
+}
@@ -0,0 +1,259 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::core::player::timer::{FeusicTimer, TimerEvent, VirtualClock};
+
+use super::Feusic;
+
+/// Transitions stop being simulated after this many, in case the waits of a cycle are all 0.
+const MAX_TRANSITIONS: usize = 1_000_000;
+
+/// What a feusic does over some time, see [`Feusic::simulate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    pub start: usize,
+    pub transitions: Vec<SimulatedTransition>,
+    /// Time spent in every music, or state in layered mode.
+    pub time_per_node: Vec<Duration>,
+    /// When the feusic ended, its duration and the wait for the next bar or beat.
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatedTransition {
+    /// Time since the feusic started when the crossfade starts.
+    pub at: Duration,
+    pub from: usize,
+    pub to: usize,
+    /// Wait chosen before the transition, without the time waited for the next bar or beat.
+    pub wait: Duration,
+}
+
+impl<M> Feusic<M> {
+    /// Runs the timer of the player for `duration` on a virtual clock, without playing the
+    /// musics. The parameters keep their default value and the duration of the feusic is
+    /// ignored. `musics_durations` are needed for the waits in loops of the musics without a
+    /// loop region, see [`Feusic::resolved_transitions`], and to know where the musics are in
+    /// their loop when the transitions wait for the next bar or beat.
+    pub fn simulate(
+        &self,
+        duration: Duration,
+        musics_durations: &[Duration],
+        rng: &mut impl Rng,
+    ) -> Simulation {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.default)
+            .collect::<Vec<_>>();
+        let start = self.choose_start(rng);
+        let mut timer = FeusicTimer::new(VirtualClock::default(), StdRng::from_rng(rng));
+        timer.reset(
+            start,
+            Some(duration),
+            self.resolved_transitions(musics_durations),
+            self.tempo,
+            parameters,
+            self.shuffled_nodes(),
+        );
+
+        let mut time_per_node = vec![Duration::ZERO; self.transitions().len()];
+        let mut simulated = vec![];
+        let mut node = start;
+        // When the crossfade to the current node started.
+        let mut entered = Duration::ZERO;
+        let mut end = duration;
+
+        while simulated.len() < MAX_TRANSITIONS {
+            let Some(now) = timer.next_tick_time() else {
+                break;
+            };
+            timer.clock_mut().now = now;
+
+            match timer.tick(self.audible_music_position(node, now, musics_durations)) {
+                Some(TimerEvent::Transition(crossfade)) => {
+                    let at = now + crossfade.delay;
+                    time_per_node[node] += at - entered;
+                    simulated.push(SimulatedTransition {
+                        at,
+                        from: node,
+                        to: crossfade.target_music,
+                        wait: now - entered,
+                    });
+
+                    node = crossfade.target_music;
+                    entered = at;
+                }
+                Some(TimerEvent::End(delay)) => {
+                    end = now + delay;
+                    break;
+                }
+                None => {}
+            }
+        }
+
+        if let Some(time) = time_per_node.get_mut(node) {
+            *time += end.saturating_sub(entered);
+        }
+
+        Simulation {
+            start,
+            transitions: simulated,
+            time_per_node,
+            duration: end,
+        }
+    }
+
+    /// Position of the audible music `time` after the feusic started. Like in the player, all
+    /// the musics start with the feusic and loop on their loop region, or whole without one.
+    fn audible_music_position(
+        &self,
+        node: usize,
+        time: Duration,
+        musics_durations: &[Duration],
+    ) -> Duration {
+        // The layers play in sync, the first one tells the position.
+        let music_index = if self.is_layered() { 0 } else { node };
+        let seconds = time.as_secs_f64();
+
+        match self.loop_region(music_index) {
+            Some(region) if region.end > region.start && seconds >= region.end => {
+                Duration::from_secs_f64(
+                    region.start + (seconds - region.start) % (region.end - region.start),
+                )
+            }
+            Some(_) => time,
+            None => match musics_durations.get(music_index) {
+                Some(music_duration) if !music_duration.is_zero() => {
+                    Duration::from_secs_f64(seconds % music_duration.as_secs_f64())
+                }
+                _ => time,
+            },
+        }
+    }
+}
+
+impl Simulation {
+    /// How many times each transition was taken, by the nodes it goes from and to.
+    pub fn transition_counts(&self) -> BTreeMap<(usize, usize), usize> {
+        let mut counts = BTreeMap::new();
+        for transition in self.transitions.iter() {
+            *counts.entry((transition.from, transition.to)).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::core::feusic::{
+        loader::FeusicMusicLoader,
+        test_utils::{load, NUMBERED_MUSICS},
+    };
+
+    use super::*;
+
+    fn feusic(feusic_toml: &str) -> Feusic<FeusicMusicLoader> {
+        load(&NUMBERED_MUSICS[..3], feusic_toml).unwrap()
+    }
+
+    #[test]
+    fn simulation_follows_the_waits() {
+        let feusic = feusic(
+            r#"
+                timing = "s0|0:w60000:1|1:w30000:0"
+                duration = 600
+            "#,
+        );
+
+        let simulation =
+            feusic.simulate(Duration::from_secs(200), &[], &mut StdRng::seed_from_u64(0));
+
+        let transitions = simulation
+            .transitions
+            .iter()
+            .map(|transition| (transition.at.as_secs(), transition.from, transition.to))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            vec![(60, 0, 1), (90, 1, 0), (150, 0, 1), (180, 1, 0)]
+        );
+        assert_eq!(
+            simulation.time_per_node,
+            vec![
+                Duration::from_secs(140),
+                Duration::from_secs(60),
+                Duration::ZERO
+            ]
+        );
+        assert_eq!(simulation.transition_counts()[&(0, 1)], 2);
+    }
+
+    #[test]
+    fn simulation_waits_for_the_next_bar() {
+        let feusic = feusic(
+            r#"
+                timing = "s0|0:w1100:1|1:w1100:0"
+                duration = 600
+                bpm = 120.0
+            "#,
+        );
+
+        let simulation =
+            feusic.simulate(Duration::from_secs(5), &[], &mut StdRng::seed_from_u64(0));
+
+        let transitions = simulation
+            .transitions
+            .iter()
+            .map(|transition| transition.at)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            vec![Duration::from_secs(2), Duration::from_secs(4)]
+        );
+    }
+
+    #[test]
+    fn simulation_measures_the_bars_in_the_loop_of_the_music() {
+        let feusic = feusic(
+            r#"
+                timing = "s0|0:w3500:1|1:w3500:0"
+                duration = 600
+                bpm = 120.0
+            "#,
+        );
+
+        // At 3.5s the music is 0.5s into its second loop, the next bar is 1.5s later.
+        let simulation = feusic.simulate(
+            Duration::from_secs(6),
+            &[Duration::from_secs(3); 3],
+            &mut StdRng::seed_from_u64(0),
+        );
+
+        assert_eq!(simulation.transitions[0].at, Duration::from_secs(5));
+        assert_eq!(simulation.transitions[0].wait, Duration::from_millis(3500));
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_simulation() {
+        let feusic = feusic(
+            r#"
+                timing = "s0;p1/1;p1/2;p1|0:w1000-9000;p1:1/w1000-9000;p1:2|1:w500-5000:0|2:w2000-3000:1"
+                duration = 600
+            "#,
+        );
+        let simulate = |seed| {
+            feusic.simulate(
+                Duration::from_secs(3600),
+                &[],
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+
+        assert_eq!(simulate(7), simulate(7));
+        assert_ne!(simulate(7), simulate(8));
+    }
+}
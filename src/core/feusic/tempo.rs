@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// Tempo of the musics of a feusic, all of them are expected to share it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+    pub bpm: f64,
+    pub beats_per_bar: u32,
+    /// Position in seconds of the first beat of the first bar.
+    pub beat_offset: f64,
+    pub quantize: Quantize,
+}
+
+/// Boundary a due transition waits for before crossfading.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantize {
+    None,
+    Beat,
+    Bar,
+}
+
+impl Tempo {
+    pub fn beat_duration(&self) -> f64 {
+        60.0 / self.bpm
+    }
+
+    pub fn bar_duration(&self) -> f64 {
+        self.beat_duration() * self.beats_per_bar as f64
+    }
+
+    /// Seconds from the playback `position` to the next beat or bar boundary, depending on
+    /// [`Tempo::quantize`]. It is 0 when not quantizing or when already on a boundary.
+    pub fn time_to_next_boundary(&self, position: f64) -> f64 {
+        let boundary_length = match self.quantize {
+            Quantize::None => return 0.0,
+            Quantize::Beat => self.beat_duration(),
+            Quantize::Bar => self.bar_duration(),
+        };
+
+        let since_offset = position - self.beat_offset;
+        if since_offset < 0.0 {
+            return -since_offset;
+        }
+
+        let into_boundary = since_offset % boundary_length;
+        if into_boundary < BOUNDARY_TOLERANCE
+            || boundary_length - into_boundary < BOUNDARY_TOLERANCE
+        {
+            0.0
+        } else {
+            boundary_length - into_boundary
+        }
+    }
+}
+
+/// Distance in seconds from a boundary under which a position is considered on it.
+const BOUNDARY_TOLERANCE: f64 = 0.001;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tempo(quantize: Quantize) -> Tempo {
+        Tempo {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            beat_offset: 0.25,
+            quantize,
+        }
+    }
+
+    #[test]
+    fn time_to_next_boundary() {
+        let bar = tempo(Quantize::Bar);
+        assert_eq!(bar.time_to_next_boundary(0.0), 0.25);
+        assert_eq!(bar.time_to_next_boundary(0.25), 0.0);
+        assert_eq!(bar.time_to_next_boundary(1.25), 1.0);
+        assert_eq!(bar.time_to_next_boundary(2.5), 1.75);
+
+        let beat = tempo(Quantize::Beat);
+        assert_eq!(beat.time_to_next_boundary(1.0), 0.25);
+        assert_eq!(beat.time_to_next_boundary(2.25), 0.0);
+
+        let none = tempo(Quantize::None);
+        assert_eq!(none.time_to_next_boundary(1.0), 0.0);
+    }
+}
@@ -0,0 +1,48 @@
+use std::error::Error;
+
+use super::{loader::FeusicMusicLoader, selection::Constraints, timing::Wait, Fade, Feusic, Next};
+
+/// Musics named by their index, for the feusics whose file names do not matter.
+pub const NUMBERED_MUSICS: [&str; 5] = ["0.mp3", "1.mp3", "2.mp3", "3.mp3", "4.mp3"];
+
+/// Loads a feusic from its feusic.toml, as if it was in a folder with the files `files_names`.
+pub fn load(
+    files_names: &[&str],
+    feusic_toml: &str,
+) -> Result<Feusic<FeusicMusicLoader>, Box<dyn Error>> {
+    let files_names = files_names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+
+    Feusic::from_feusic(
+        "feusic".to_string(),
+        &files_names,
+        feusic_toml.to_string(),
+        |music_name| FeusicMusicLoader::FolderFeusic {
+            music_path: music_name,
+        },
+    )
+}
+
+/// Loads the feusic.toml `feusic` serializes to, with the same files.
+pub fn reload(
+    feusic: &Feusic<FeusicMusicLoader>,
+    files_names: &[&str],
+) -> Feusic<FeusicMusicLoader> {
+    load(files_names, &feusic.to_feusic_toml().unwrap()).unwrap()
+}
+
+/// Transition to `target_music` after `wait` milliseconds, with the defaults of everything
+/// else.
+pub fn next(target_music: usize, wait: (usize, usize)) -> Next {
+    Next {
+        probability_weight: 100,
+        target_music,
+        wait: (Wait::millis(wait.0), Wait::millis(wait.1)),
+        fade: Fade::default(),
+        stinger: None,
+        when: None,
+        constraints: Constraints::default(),
+    }
+}
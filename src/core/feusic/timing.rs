@@ -0,0 +1,1167 @@
+use std::{error::Error, fmt::Display, iter::Peekable, str::Chars};
+
+use std::time::Duration;
+
+use super::{
+    names::{MusicNameError, MusicNames},
+    parameters::Condition,
+    selection::Constraints,
+    tempo::Tempo,
+    Fade, FadeEasing, Feusic, Next, Start, Stinger,
+};
+
+pub(super) struct ParsedTiming<'a> {
+    pub start: Vec<Start>,
+    pub timing_musics: ParsedTimingMusicIterator<'a>,
+}
+
+pub(super) struct ParsedTimingMusic {
+    pub music_index: usize,
+    pub choices: Vec<ParsedTimingChoice>,
+}
+
+pub(super) struct ParsedTimingChoice {
+    pub probability_weight: usize,
+    pub target_music_index: usize,
+    pub wait: (Wait, Wait),
+    pub fade: Option<usize>,
+    pub easing: Option<FadeEasing>,
+    pub stinger: Option<Stinger>,
+    pub when: Option<Condition>,
+    pub constraints: Constraints,
+}
+
+pub(super) struct ParsedTimingMusicIterator<'a> {
+    chars: TimingChars<'a>,
+    names: &'a MusicNames,
+}
+
+/// Wait before a transition, written in a timing string as an amount followed by its unit,
+/// e.g. `120000`, `2m` or `4L`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wait {
+    pub amount: usize,
+    pub unit: WaitUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitUnit {
+    Milliseconds,
+    Seconds,
+    Minutes,
+    /// Bars of the feusic tempo.
+    Bars,
+    /// Loops of the loop region of the music the transition leaves.
+    Loops,
+}
+
+/// Suffixes of the wait units, milliseconds have none.
+const WAIT_UNITS: [(char, WaitUnit); 4] = [
+    ('s', WaitUnit::Seconds),
+    ('m', WaitUnit::Minutes),
+    ('b', WaitUnit::Bars),
+    ('L', WaitUnit::Loops),
+];
+
+/// Token the timing parser was looking for when it failed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingToken {
+    Start,
+    MusicSeparator,
+    Colon,
+    Number,
+    MusicName,
+    ChoiceDefinition,
+    Probability,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimingParseError {
+    /// A different character than the `expected` one was found, `None` is the end of the timing.
+    UnexpectedToken {
+        offset: usize,
+        expected: TimingToken,
+        found: Option<char>,
+    },
+    /// A choice has no `w` wait definition.
+    MissingWait { offset: usize, found: Option<char> },
+    /// A music has multiple choices and some of them have no `p` probability, or a choice has a
+    /// 0 one.
+    MissingProbability { offset: usize, found: Option<char> },
+    /// A `@name` does not reference exactly one music of the feusic.
+    UnresolvedMusic {
+        offset: usize,
+        error: MusicNameError,
+    },
+    /// A number, or a wait once in milliseconds, does not fit in a `usize`.
+    NumberTooLarge { offset: usize, found: Option<char> },
+}
+
+/// A [`TimingParseError`] together with the timing string it happened in.
+#[derive(Debug)]
+pub struct InvalidTimingError {
+    pub timing: String,
+    pub error: TimingParseError,
+}
+
+/// Characters of a timing string that keep track of the offset of the next one.
+struct TimingChars<'a> {
+    chars: Peekable<Chars<'a>>,
+    offset: usize,
+}
+
+impl<'a> ParsedTiming<'a> {
+    /// Parses a timing string, musics are referenced either by index or by `@name`, names are
+    /// resolved with `names`.
+    pub fn try_from(s: &'a str, names: &'a MusicNames) -> Result<Self, TimingParseError> {
+        let mut chars = TimingChars {
+            chars: s.chars().peekable(),
+            offset: 0,
+        };
+
+        match chars.next() {
+            Some('s') => {}
+            found => {
+                return Err(TimingParseError::UnexpectedToken {
+                    offset: 0,
+                    expected: TimingToken::Start,
+                    found,
+                })
+            }
+        }
+
+        let start = read_start(&mut chars, names)?;
+
+        Ok(Self {
+            start,
+            timing_musics: ParsedTimingMusicIterator { chars, names },
+        })
+    }
+}
+
+impl<'a> Iterator for ParsedTimingMusicIterator<'a> {
+    type Item = Result<ParsedTimingMusic, TimingParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let music_offset = self.chars.offset;
+
+        match self.chars.next() {
+            Some('|') => {}
+            None => return None,
+            found => {
+                return Some(Err(TimingParseError::UnexpectedToken {
+                    offset: music_offset,
+                    expected: TimingToken::MusicSeparator,
+                    found,
+                }))
+            }
+        }
+
+        Some(self.read_music())
+    }
+}
+
+impl<'a> ParsedTimingMusicIterator<'a> {
+    fn read_music(&mut self) -> Result<ParsedTimingMusic, TimingParseError> {
+        let mut choices = vec![];
+
+        let music_index = read_music(&mut self.chars, self.names)?;
+        self.chars.expect(':', TimingToken::Colon)?;
+
+        let mut wait = None;
+        let mut probability_weight = None;
+
+        let mut choices_with_probability = 0;
+        // Offset and first character of every choice, with whether it has a probability.
+        let mut choices_positions = vec![];
+        loop {
+            let choice_offset = self.chars.offset;
+            let choice_found = self.chars.peek();
+            let probabilities_before = choices_with_probability;
+            let mut fade = None;
+
+            loop {
+                let offset = self.chars.offset;
+                match self.chars.next() {
+                    Some('w') => {
+                        let wait_lower = read_wait(&mut self.chars)?;
+                        let wait_higher = match self.chars.peek() {
+                            Some('-') => {
+                                self.chars.next();
+                                read_wait(&mut self.chars)?
+                            }
+                            _ => wait_lower,
+                        };
+                        wait = Some((wait_lower, wait_higher));
+                    }
+                    Some('p') => {
+                        choices_with_probability += 1;
+                        probability_weight = Some(read_number(&mut self.chars)?);
+                    }
+                    Some('f') => {
+                        fade = Some(read_number(&mut self.chars)?);
+                    }
+                    found => {
+                        return Err(TimingParseError::UnexpectedToken {
+                            offset,
+                            expected: TimingToken::ChoiceDefinition,
+                            found,
+                        })
+                    }
+                }
+
+                match self.chars.peek() {
+                    Some(';') => {
+                        self.chars.next();
+                    }
+                    _ => break,
+                }
+            }
+
+            let Some(wait) = wait else {
+                return Err(TimingParseError::MissingWait {
+                    offset: self.chars.offset,
+                    found: self.chars.peek(),
+                });
+            };
+
+            self.chars.expect(':', TimingToken::Colon)?;
+
+            let target_music_index = read_music(&mut self.chars, self.names)?;
+
+            choices.push(ParsedTimingChoice {
+                probability_weight: probability_weight.unwrap_or(100),
+                target_music_index,
+                wait,
+                fade,
+                easing: None,
+                stinger: None,
+                when: None,
+                constraints: Constraints::default(),
+            });
+            choices_positions.push((
+                choice_offset,
+                choice_found,
+                choices_with_probability > probabilities_before,
+            ));
+
+            match self.chars.peek() {
+                Some('/') => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+
+        let missing_probability =
+            choices
+                .iter()
+                .zip(&choices_positions)
+                .find(|(choice, (_, _, has_probability))| {
+                    choice.probability_weight == 0 || (choices.len() > 1 && !has_probability)
+                });
+        if let Some((_, &(offset, found, _))) = missing_probability {
+            return Err(TimingParseError::MissingProbability { offset, found });
+        }
+
+        Ok(ParsedTimingMusic {
+            music_index,
+            choices,
+        })
+    }
+}
+
+impl<'a> TimingChars<'a> {
+    fn next(&mut self) -> Option<char> {
+        let next = self.chars.next();
+        if next.is_some() {
+            self.offset += 1;
+        }
+        next
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn expect(
+        &mut self,
+        expected_char: char,
+        expected: TimingToken,
+    ) -> Result<(), TimingParseError> {
+        let offset = self.offset;
+        match self.next() {
+            Some(c) if c == expected_char => Ok(()),
+            found => Err(TimingParseError::UnexpectedToken {
+                offset,
+                expected,
+                found,
+            }),
+        }
+    }
+}
+
+impl<M> Feusic<M> {
+    /// Serializes the transitions of the feusic into a canonical timing string, parsing it
+    /// back with the same `fade` and `fade_easing` gives the same start and musics transitions.
+    /// Fails when the feusic has transitions a timing string cannot hold, with states, stingers,
+    /// conditions, constraints or their own fade easing, only [`Feusic::to_feusic_toml`] can
+    /// write those.
+    pub fn to_timing_string(&self) -> Result<String, Box<dyn Error>> {
+        if self.is_layered() {
+            return Err("the transitions of a layered feusic cannot be written in a timing string, they go from state to state".into());
+        }
+
+        for (music_index, music) in self.musics.iter().enumerate() {
+            for next in music.next_choices.iter() {
+                let unsupported = if next.stinger.is_some() {
+                    "a stinger"
+                } else if next.when.is_some() {
+                    "a `when` condition"
+                } else if next.constraints != Constraints::default() {
+                    "constraints"
+                } else if next.fade.easing != self.fade.easing {
+                    "its own fade easing"
+                } else {
+                    continue;
+                };
+
+                return Err(format!(
+                    "music {} goes to music {} with {}, a timing string cannot hold it",
+                    music_index, next.target_music, unsupported
+                )
+                .into());
+            }
+        }
+
+        let mut timing = self.timing_start();
+        for (music_index, music) in self.musics.iter().enumerate() {
+            if !music.next_choices.is_empty() {
+                timing.push_str(&format!(
+                    "|{}:{}",
+                    music_index,
+                    self.timing_choices(&music.next_choices)
+                ));
+            }
+        }
+
+        Ok(timing)
+    }
+
+    /// Start of a timing string, e.g. `s0` or `s0;p70/1;p30`.
+    pub(super) fn timing_start(&self) -> String {
+        let start = self
+            .start
+            .iter()
+            .map(|start| {
+                if self.start.len() > 1 || start.probability_weight != 100 {
+                    format!("{};p{}", start.node, start.probability_weight)
+                } else {
+                    start.node.to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        format!("s{}", start.join("/"))
+    }
+
+    /// Choices of a music in a timing string, e.g. `w5000:1/w2m-3m;p30;f200:0`. Only their
+    /// waits, weights, fade durations and targets are written.
+    pub(super) fn timing_choices(&self, next_choices: &[Next]) -> String {
+        next_choices
+            .iter()
+            .map(|next| {
+                let mut choice = format!("w{}", next.wait.0);
+                if next.wait.1 != next.wait.0 {
+                    choice.push_str(&format!("-{}", next.wait.1));
+                }
+                if next_choices.len() > 1 || next.probability_weight != 100 {
+                    choice.push_str(&format!(";p{}", next.probability_weight));
+                }
+                if next.fade.duration != self.fade.duration {
+                    choice.push_str(&format!(";f{}", next.fade.duration.as_millis()));
+                }
+                format!("{}:{}", choice, next.target_music)
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl ParsedTimingChoice {
+    /// Converts the choice into a [`Next`], using the feusic default fade for what the choice
+    /// does not define and the constraints of the target, from `targets_constraints`, for the
+    /// ones it does not define.
+    pub fn into_next(self, default_fade: Fade, targets_constraints: &[Constraints]) -> Next {
+        Next {
+            probability_weight: self.probability_weight,
+            target_music: self.target_music_index,
+            wait: self.wait,
+            fade: Fade {
+                duration: self
+                    .fade
+                    .map(|fade| Duration::from_millis(fade as u64))
+                    .unwrap_or(default_fade.duration),
+                easing: self.easing.unwrap_or(default_fade.easing),
+            },
+            stinger: self.stinger,
+            when: self.when,
+            constraints: self.constraints.or(targets_constraints
+                .get(self.target_music_index)
+                .copied()
+                .unwrap_or_default()),
+        }
+    }
+}
+
+/// Reads a music index or a `@name`, the name goes on until the next separator.
+fn read_music(chars: &mut TimingChars, names: &MusicNames) -> Result<usize, TimingParseError> {
+    if chars.peek() != Some('@') {
+        return read_number(chars);
+    }
+
+    let offset = chars.offset;
+    chars.next();
+
+    let mut name = String::new();
+    while let Some(c) = chars.peek() {
+        if matches!(c, '|' | ':' | '/' | ';') {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+
+    if name.is_empty() {
+        return Err(TimingParseError::UnexpectedToken {
+            offset: chars.offset,
+            expected: TimingToken::MusicName,
+            found: chars.peek(),
+        });
+    }
+
+    names
+        .resolve(&name)
+        .map_err(|error| TimingParseError::UnresolvedMusic { offset, error })
+}
+
+/// Reads the musics a timing can start from, separated by `/` and each with a `p` probability
+/// when there are more than one, e.g. `0;p70/2;p30`.
+fn read_start(chars: &mut TimingChars, names: &MusicNames) -> Result<Vec<Start>, TimingParseError> {
+    let mut start = vec![];
+    // Offset and first character of every start, with whether it has a probability.
+    let mut starts_positions = vec![];
+
+    loop {
+        let offset = chars.offset;
+        let found = chars.peek();
+        let node = read_music(chars, names)?;
+        let probability_weight = match chars.peek() {
+            Some(';') => {
+                chars.next();
+                chars.expect('p', TimingToken::Probability)?;
+                starts_positions.push((offset, found, true));
+                read_number(chars)?
+            }
+            _ => {
+                starts_positions.push((offset, found, false));
+                100
+            }
+        };
+        start.push(Start {
+            node,
+            probability_weight,
+        });
+
+        match chars.peek() {
+            Some('/') => {
+                chars.next();
+            }
+            _ => break,
+        }
+    }
+
+    let missing_probability =
+        start
+            .iter()
+            .zip(&starts_positions)
+            .find(|(start_choice, (_, _, has_probability))| {
+                start_choice.probability_weight == 0 || (start.len() > 1 && !has_probability)
+            });
+    if let Some((_, &(offset, found, _))) = missing_probability {
+        return Err(TimingParseError::MissingProbability { offset, found });
+    }
+
+    Ok(start)
+}
+
+/// Reads a number followed by an optional [`WaitUnit`] suffix.
+fn read_wait(chars: &mut TimingChars) -> Result<Wait, TimingParseError> {
+    let offset = chars.offset;
+    let found = chars.peek();
+    let amount = read_number(chars)?;
+    let unit = match WAIT_UNITS
+        .iter()
+        .find(|(suffix, _)| chars.peek() == Some(*suffix))
+    {
+        Some((_, unit)) => {
+            chars.next();
+            *unit
+        }
+        None => WaitUnit::Milliseconds,
+    };
+
+    if millis_per_unit(unit).is_some_and(|millis| amount.checked_mul(millis).is_none()) {
+        return Err(TimingParseError::NumberTooLarge { offset, found });
+    }
+
+    Ok(Wait { amount, unit })
+}
+
+/// Milliseconds in one of `unit`, `None` for the units that depend on the tempo or the music.
+fn millis_per_unit(unit: WaitUnit) -> Option<usize> {
+    match unit {
+        WaitUnit::Milliseconds => Some(1),
+        WaitUnit::Seconds => Some(1000),
+        WaitUnit::Minutes => Some(60000),
+        WaitUnit::Bars | WaitUnit::Loops => None,
+    }
+}
+
+fn read_number(chars: &mut TimingChars) -> Result<usize, TimingParseError> {
+    let offset = chars.offset;
+    let first = chars.peek();
+    let mut n: usize = 0;
+    let mut found = false;
+
+    while let Some(maybe_number) = chars.peek() {
+        if let Some(number) = maybe_number.to_digit(10) {
+            found = true;
+            n = n
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(number as usize))
+                .ok_or(TimingParseError::NumberTooLarge {
+                    offset,
+                    found: first,
+                })?;
+            chars.next();
+        } else {
+            break;
+        }
+    }
+
+    if found {
+        Ok(n)
+    } else {
+        Err(TimingParseError::UnexpectedToken {
+            offset,
+            expected: TimingToken::Number,
+            found: chars.peek(),
+        })
+    }
+}
+
+impl TimingParseError {
+    /// Character offset in the timing string where the error happened.
+    pub fn offset(&self) -> usize {
+        match self {
+            TimingParseError::UnexpectedToken { offset, .. }
+            | TimingParseError::MissingWait { offset, .. }
+            | TimingParseError::MissingProbability { offset, .. }
+            | TimingParseError::UnresolvedMusic { offset, .. }
+            | TimingParseError::NumberTooLarge { offset, .. } => *offset,
+        }
+    }
+
+    /// Description of what the parser expected at [`TimingParseError::offset`].
+    pub fn expected(&self) -> String {
+        match self {
+            TimingParseError::UnexpectedToken { expected, .. } => expected.to_string(),
+            TimingParseError::MissingWait { .. } => "a wait definition 'w'".to_string(),
+            TimingParseError::MissingProbability { .. } => {
+                "a probability 'p' different than 0 for every choice".to_string()
+            }
+            TimingParseError::UnresolvedMusic { .. } => "a music of the feusic".to_string(),
+            TimingParseError::NumberTooLarge { .. } => "a smaller number".to_string(),
+        }
+    }
+
+    /// Character found at [`TimingParseError::offset`], `None` is the end of the timing.
+    pub fn found(&self) -> Option<char> {
+        match self {
+            TimingParseError::UnexpectedToken { found, .. }
+            | TimingParseError::MissingWait { found, .. }
+            | TimingParseError::MissingProbability { found, .. }
+            | TimingParseError::NumberTooLarge { found, .. } => *found,
+            TimingParseError::UnresolvedMusic { .. } => Some('@'),
+        }
+    }
+
+    /// Renders the timing string with a caret under the column where the error happened.
+    ///
+    /// ```text
+    /// s0|0:w5000;x:1
+    ///            ^ expected 'w', 'p' or 'f' but found 'x' at character 11
+    /// ```
+    pub fn render(&self, timing: &str) -> String {
+        format!("{}\n{}^ {}", timing, " ".repeat(self.offset()), self)
+    }
+}
+
+impl Display for TimingParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let found = match self.found() {
+            Some(c) => format!("'{}'", c),
+            None => "the end of the timing".to_string(),
+        };
+
+        match self {
+            TimingParseError::MissingProbability { offset, .. } => write!(
+                f,
+                "expected {} in the music at character {}, e.g. s0|0:w120000-150000:1|1:w15000-60000;p30:2/w5000-10000;p70:0|2:w6000-20000:1",
+                self.expected(),
+                offset
+            ),
+            TimingParseError::UnresolvedMusic { offset, error } => {
+                write!(f, "{} at character {}", error, offset)
+            }
+            TimingParseError::NumberTooLarge { offset, .. } => {
+                write!(f, "number too large at character {}", offset)
+            }
+            _ => write!(
+                f,
+                "expected {} but found {} at character {}",
+                self.expected(),
+                found,
+                self.offset()
+            ),
+        }
+    }
+}
+
+impl Error for TimingParseError {}
+
+impl Display for TimingToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimingToken::Start => write!(f, "'s'"),
+            TimingToken::MusicSeparator => write!(f, "'|'"),
+            TimingToken::Colon => write!(f, "':'"),
+            TimingToken::Number => write!(f, "a number"),
+            TimingToken::MusicName => write!(f, "a music name"),
+            TimingToken::ChoiceDefinition => write!(f, "'w', 'p' or 'f'"),
+            TimingToken::Probability => write!(f, "'p'"),
+        }
+    }
+}
+
+impl Wait {
+    pub fn millis(amount: usize) -> Self {
+        Self {
+            amount,
+            unit: WaitUnit::Milliseconds,
+        }
+    }
+
+    /// Parses a wait written like in a timing string, e.g. `90s`.
+    pub fn parse(wait: &str) -> Result<Self, String> {
+        let mut chars = TimingChars {
+            chars: wait.chars().peekable(),
+            offset: 0,
+        };
+
+        match read_wait(&mut chars) {
+            Ok(parsed_wait) if chars.peek().is_none() => Ok(parsed_wait),
+            _ => Err(format!(
+                "expected a wait like `90000`, `90s`, `2m`, `8b` or `4L`, found `{}`",
+                wait
+            )),
+        }
+    }
+
+    /// Milliseconds of the wait when they depend neither on the tempo nor on the music.
+    /// Parsed waits always fit, the other ones saturate.
+    pub fn fixed_millis(&self) -> Option<usize> {
+        millis_per_unit(self.unit).map(|millis| self.amount.saturating_mul(millis))
+    }
+
+    /// Milliseconds of the wait, bars last a bar of the `tempo` and loops `loop_duration`.
+    /// Waits too long for a `usize` saturate.
+    pub fn resolve(&self, tempo: Option<Tempo>, loop_duration: Duration) -> usize {
+        match self.unit {
+            WaitUnit::Bars => {
+                let bar_duration = tempo.map(|tempo| tempo.bar_duration()).unwrap_or(0.0);
+                (bar_duration * 1000.0 * self.amount as f64).round() as usize
+            }
+            WaitUnit::Loops => (loop_duration.as_millis() as usize).saturating_mul(self.amount),
+            _ => self.fixed_millis().unwrap_or(self.amount),
+        }
+    }
+
+    /// Whether the wait is longer than `other` whatever the tempo and the music, waits in
+    /// bars or loops only compare with waits in the same unit.
+    pub fn is_longer_than(&self, other: &Wait) -> bool {
+        match (self.fixed_millis(), other.fixed_millis()) {
+            (Some(millis), Some(other_millis)) => millis > other_millis,
+            _ => self.unit == other.unit && self.amount > other.amount,
+        }
+    }
+}
+
+impl Display for Wait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.amount)?;
+        match WAIT_UNITS.iter().find(|(_, unit)| *unit == self.unit) {
+            Some((suffix, _)) => write!(f, "{}", suffix),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for InvalidTimingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid timing.\n{}", self.error.render(&self.timing))
+    }
+}
+
+impl Error for InvalidTimingError {}
+
+#[cfg(test)]
+mod test {
+    use crate::core::feusic::{
+        tempo::Quantize,
+        test_utils::{load, reload, NUMBERED_MUSICS},
+    };
+
+    use super::*;
+
+    fn millis(wait_lower: usize, wait_higher: usize) -> (Wait, Wait) {
+        (Wait::millis(wait_lower), Wait::millis(wait_higher))
+    }
+
+    #[test]
+    fn parse_complex_timing() {
+        let timing = "s0|0:w120000-180000;p8:1/w2000-5000;p2:4|1:w10000-20000;p1:2/w10000-20000;p1:3/w10000-20000;p1:1|2:w5000-10000:1|3:w5000-10000:1|4:w120000-180000:1";
+        let names = MusicNames::default();
+        let parsed_timing = ParsedTiming::try_from(timing, &names).unwrap();
+
+        let timing_musics: Vec<_> = parsed_timing
+            .timing_musics
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(timing_musics.len(), 5);
+
+        assert_eq!(timing_musics[0].music_index, 0);
+        assert_eq!(timing_musics[0].choices.len(), 2);
+        assert_eq!(timing_musics[0].choices[0].probability_weight, 8);
+        assert_eq!(timing_musics[0].choices[0].target_music_index, 1);
+        assert_eq!(timing_musics[0].choices[0].wait, millis(120000, 180000));
+        assert_eq!(timing_musics[0].choices[1].probability_weight, 2);
+        assert_eq!(timing_musics[0].choices[1].target_music_index, 4);
+        assert_eq!(timing_musics[0].choices[1].wait, millis(2000, 5000));
+
+        assert_eq!(timing_musics[1].music_index, 1);
+        assert_eq!(timing_musics[1].choices.len(), 3);
+        assert_eq!(timing_musics[1].choices[0].probability_weight, 1);
+        assert_eq!(timing_musics[1].choices[0].target_music_index, 2);
+        assert_eq!(timing_musics[1].choices[0].wait, millis(10000, 20000));
+        assert_eq!(timing_musics[1].choices[1].probability_weight, 1);
+        assert_eq!(timing_musics[1].choices[1].target_music_index, 3);
+        assert_eq!(timing_musics[1].choices[1].wait, millis(10000, 20000));
+        assert_eq!(timing_musics[1].choices[2].probability_weight, 1);
+        assert_eq!(timing_musics[1].choices[2].target_music_index, 1);
+        assert_eq!(timing_musics[1].choices[2].wait, millis(10000, 20000));
+
+        assert_eq!(timing_musics[2].music_index, 2);
+        assert_eq!(timing_musics[2].choices.len(), 1);
+        assert_eq!(timing_musics[2].choices[0].probability_weight, 100);
+        assert_eq!(timing_musics[2].choices[0].target_music_index, 1);
+        assert_eq!(timing_musics[2].choices[0].wait, millis(5000, 10000));
+
+        assert_eq!(timing_musics[3].music_index, 3);
+        assert_eq!(timing_musics[3].choices.len(), 1);
+        assert_eq!(timing_musics[3].choices[0].probability_weight, 100);
+        assert_eq!(timing_musics[3].choices[0].target_music_index, 1);
+        assert_eq!(timing_musics[3].choices[0].wait, millis(5000, 10000));
+
+        assert_eq!(timing_musics[4].music_index, 4);
+        assert_eq!(timing_musics[4].choices.len(), 1);
+        assert_eq!(timing_musics[4].choices[0].probability_weight, 100);
+        assert_eq!(timing_musics[4].choices[0].target_music_index, 1);
+        assert_eq!(timing_musics[4].choices[0].wait, millis(120000, 180000));
+    }
+
+    #[test]
+    fn parse_error_points_at_the_failing_character() {
+        let timing = "s0|0:w5000;x:1";
+        let error = ParsedTiming::try_from(timing, &MusicNames::default())
+            .unwrap()
+            .timing_musics
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error,
+            TimingParseError::UnexpectedToken {
+                offset: 11,
+                expected: TimingToken::ChoiceDefinition,
+                found: Some('x'),
+            }
+        );
+        assert_eq!(
+            error.render(timing),
+            "s0|0:w5000;x:1\n           ^ expected 'w', 'p' or 'f' but found 'x' at character 11"
+        );
+    }
+
+    #[test]
+    fn parse_music_names() {
+        let musics_names = vec!["map.mp3".to_string(), "battle theme.mp3".to_string()];
+        let names = MusicNames::new(&musics_names, &Default::default()).unwrap();
+        let parsed_timing =
+            ParsedTiming::try_from("s@map|@map:w60000:@battle theme|1:w5000:0", &names).unwrap();
+
+        assert_eq!(
+            parsed_timing.start,
+            vec![Start {
+                node: 0,
+                probability_weight: 100
+            }]
+        );
+
+        let timing_musics: Vec<_> = parsed_timing
+            .timing_musics
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(timing_musics[0].music_index, 0);
+        assert_eq!(timing_musics[0].choices[0].target_music_index, 1);
+        assert_eq!(timing_musics[1].music_index, 1);
+        assert_eq!(timing_musics[1].choices[0].target_music_index, 0);
+    }
+
+    #[test]
+    fn parse_error_points_at_the_unknown_music() {
+        let timing = "s0|0:w5000:@boss";
+        let musics_names = vec!["map.mp3".to_string()];
+        let names = MusicNames::new(&musics_names, &Default::default()).unwrap();
+        let error = ParsedTiming::try_from(timing, &names)
+            .unwrap()
+            .timing_musics
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.render(timing),
+            "s0|0:w5000:@boss\n           ^ no music named @boss at character 11"
+        );
+    }
+
+    #[test]
+    fn parse_error_at_the_end_of_the_timing() {
+        let timing = "s0|0:w5000:";
+        let error = ParsedTiming::try_from(timing, &MusicNames::default())
+            .unwrap()
+            .timing_musics
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error,
+            TimingParseError::UnexpectedToken {
+                offset: 11,
+                expected: TimingToken::Number,
+                found: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_waits_with_units() {
+        let timing = "s0|0:w2m-3m:1|1:w90s-2m;p1:0/w8b;p1:1/w4L;p1:2|2:w500:0";
+        let timing_musics: Vec<_> = ParsedTiming::try_from(timing, &MusicNames::default())
+            .unwrap()
+            .timing_musics
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let waits = timing_musics
+            .iter()
+            .flat_map(|music| music.choices.iter().map(|choice| choice.wait))
+            .map(|(wait_lower, wait_higher)| format!("{}-{}", wait_lower, wait_higher))
+            .collect::<Vec<_>>();
+        assert_eq!(waits, vec!["2m-3m", "90s-2m", "8b-8b", "4L-4L", "500-500"]);
+
+        let tempo = Tempo {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            beat_offset: 0.0,
+            quantize: Quantize::Bar,
+        };
+        let loop_duration = Duration::from_secs(30);
+        let resolved = timing_musics
+            .iter()
+            .flat_map(|music| music.choices.iter().map(|choice| choice.wait.0))
+            .map(|wait| wait.resolve(Some(tempo), loop_duration))
+            .collect::<Vec<_>>();
+        assert_eq!(resolved, vec![120000, 90000, 16000, 120000, 500]);
+
+        assert!(Wait::parse("2m")
+            .unwrap()
+            .is_longer_than(&Wait::parse("90s").unwrap()));
+        assert!(!Wait::parse("2L")
+            .unwrap()
+            .is_longer_than(&Wait::parse("1m").unwrap()));
+        assert_eq!(
+            Wait::parse("2h").unwrap_err(),
+            "expected a wait like `90000`, `90s`, `2m`, `8b` or `4L`, found `2h`"
+        );
+    }
+
+    #[test]
+    fn huge_waits_fail_to_parse_or_saturate() {
+        let huge_minutes = format!("{}m", usize::MAX / 1000);
+        let error =
+            ParsedTiming::try_from(&format!("s0|0:w{}:0", huge_minutes), &MusicNames::default())
+                .unwrap()
+                .timing_musics
+                .collect::<Result<Vec<_>, _>>()
+                .err()
+                .unwrap();
+        assert_eq!(
+            error,
+            TimingParseError::NumberTooLarge {
+                offset: 6,
+                found: Some('1'),
+            }
+        );
+        assert!(Wait::parse(&huge_minutes).is_err());
+        assert!(Wait::parse("99999999999999999999999").is_err());
+
+        let huge_loops = Wait::parse(&format!("{}L", usize::MAX / 1000)).unwrap();
+        assert_eq!(
+            huge_loops.resolve(None, Duration::from_secs(30)),
+            usize::MAX
+        );
+    }
+
+    #[test]
+    fn parse_weighted_start() {
+        let musics_names = vec!["map.mp3".to_string(), "battle.mp3".to_string()];
+        let names = MusicNames::new(&musics_names, &Default::default()).unwrap();
+        let parsed_timing = ParsedTiming::try_from("s0;p70/@battle;p30|0:w5000:1", &names).unwrap();
+
+        assert_eq!(
+            parsed_timing.start,
+            vec![
+                Start {
+                    node: 0,
+                    probability_weight: 70
+                },
+                Start {
+                    node: 1,
+                    probability_weight: 30
+                },
+            ]
+        );
+
+        let timing = "s0;p70/1|0:w5000:1";
+        let error = ParsedTiming::try_from(timing, &names).err().unwrap();
+        assert_eq!(
+            error,
+            TimingParseError::MissingProbability {
+                offset: 7,
+                found: Some('1'),
+            }
+        );
+    }
+
+    #[test]
+    fn zero_weights_fail_to_parse() {
+        let names = MusicNames::default();
+
+        let error = ParsedTiming::try_from("s0|0:w5000;p0:0", &names)
+            .unwrap()
+            .timing_musics
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            TimingParseError::MissingProbability {
+                offset: 5,
+                found: Some('w'),
+            }
+        );
+
+        let error = ParsedTiming::try_from("s0|0:w10;p1:1/w5:0", &names)
+            .unwrap()
+            .timing_musics
+            .collect::<Result<Vec<_>, _>>()
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            TimingParseError::MissingProbability {
+                offset: 14,
+                found: Some('w'),
+            }
+        );
+
+        let error = ParsedTiming::try_from("s1;p50/0;p0|0:w5000:0", &names)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            TimingParseError::MissingProbability {
+                offset: 7,
+                found: Some('0'),
+            }
+        );
+    }
+
+    #[test]
+    fn serialized_feusic_parses_back_to_the_same_feusic() {
+        let timing = "s0|0:w120000-180000;p8:1/w2000-5000;p2:4|1:w10000-20000;p1:2/w10000-20000;p1:3/w10000-20000;p1:1|2:w5000-10000:1|3:w5000;f200:1|4:w120000-180000;p50;f8000:1";
+
+        let feusic = load(
+            &NUMBERED_MUSICS,
+            &format!(
+                "timing = \"{}\"\nduration = 600\nloop_start = 2.5\nloop_end = 114.5",
+                timing
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(feusic.to_timing_string().unwrap(), timing);
+
+        let from_timing_string = load(
+            &NUMBERED_MUSICS,
+            &format!(
+                "timing = \"{}\"\nduration = 600",
+                feusic.to_timing_string().unwrap()
+            ),
+        )
+        .unwrap();
+        let from_feusic_toml = reload(&feusic, &NUMBERED_MUSICS);
+
+        assert_eq!(from_feusic_toml.looping, feusic.looping);
+        for reparsed in [from_timing_string, from_feusic_toml] {
+            assert_eq!(reparsed.start, feusic.start);
+            assert_eq!(reparsed.musics.len(), feusic.musics.len());
+            for (reparsed_music, music) in reparsed.musics.iter().zip(&feusic.musics) {
+                assert_eq!(reparsed_music.name, music.name);
+                assert_eq!(reparsed_music.next_choices, music.next_choices);
+            }
+        }
+    }
+
+    #[test]
+    fn timing_string_fails_for_what_it_cannot_hold() {
+        let files_names = ["map.mp3", "battle.mp3", "cymbal.wav"];
+
+        let with_stinger = load(
+            &files_names,
+            r#"
+                start = "map"
+                duration = 600
+                fade = 500
+
+                [[music]]
+                index = "map"
+                next = [{ target = "battle", wait_min = 1000, stinger = "cymbal.wav" }]
+
+                [[music]]
+                index = "battle"
+                next = [{ target = "map", wait_min = 1000, fade = 2000 }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            with_stinger.to_timing_string().unwrap_err().to_string(),
+            "music 1 goes to music 0 with a stinger, a timing string cannot hold it"
+        );
+
+        let from_feusic_toml = reload(&with_stinger, &files_names);
+        assert_eq!(from_feusic_toml.fade, with_stinger.fade);
+        assert_eq!(from_feusic_toml.transitions(), with_stinger.transitions());
+
+        let layered = load(
+            &files_names[..2],
+            r#"
+                start = "explore"
+                duration = 600
+
+                [[state]]
+                name = "explore"
+                layers = { map = 0.0 }
+                next = [{ target = "explore", wait_min = 1000 }]
+            "#,
+        )
+        .unwrap();
+
+        assert!(layered.to_timing_string().is_err());
+        assert_eq!(reload(&layered, &files_names[..2]).states, layered.states);
+    }
+
+    #[test]
+    fn waits_resolve_with_the_tempo_and_the_loops() {
+        let files_names = ["map.mp3", "battle.mp3"];
+        let feusic_toml = r#"
+            start = "map"
+            duration = 600
+            bpm = 120
+
+            [[music]]
+            index = "map"
+
+            [[music.next]]
+            target = "battle"
+            wait_min = "8b"
+            wait_max = "2m"
+
+            [[music]]
+            index = "battle"
+            loop_start = 10.0
+            loop_end = 40.0
+
+            [[music.next]]
+            target = "map"
+            wait_min = "2L"
+        "#;
+
+        let feusic = load(&files_names, feusic_toml).unwrap();
+
+        // Musics are sorted by name: battle, map.
+        let waits = feusic
+            .resolved_transitions(&[Duration::from_secs(50), Duration::from_secs(90)])
+            .iter()
+            .map(|next_choices| next_choices[0].wait)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            waits,
+            vec![
+                (Wait::millis(60000), Wait::millis(60000)),
+                (Wait::millis(16000), Wait::millis(120000)),
+            ]
+        );
+        assert_eq!(feusic.to_timing_string().unwrap(), "s1|0:w2L:1|1:w8b-2m:0");
+
+        let from_feusic_toml = reload(&feusic, &files_names);
+
+        assert_eq!(
+            from_feusic_toml.musics[1].next_choices,
+            feusic.musics[1].next_choices
+        );
+
+        let error = load(&files_names, &feusic_toml.replace("\"8b\"", "\"3m\"")).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "[[music]] #1 (@map) [[music.next]] #1: wait_min (3m) is greater than wait_max (2m)"
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use std::{error::Error, fmt::Display};
+
+use super::{timing::WaitUnit, Feusic, Next, Start};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Returned when loading a feusic whose validation found at least one error.
+#[derive(Debug)]
+pub struct InvalidFeusicError {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl<M> Feusic<M> {
+    /// Checks the transitions graph of the feusic, made of musics or of states in layered mode.
+    ///
+    /// Errors are problems that would break the playback: musics or waits that do not exist,
+    /// transitions with a 0 weight, waits in bars without a tempo and musics with no way out in
+    /// a graph that has transitions.
+    /// Warnings are musics that are never played because they cannot be reached.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+        let transitions = self.transitions();
+        let nodes_count = transitions.len();
+        let node = if self.is_layered() { "state" } else { "music" };
+
+        for start in self.start.iter().filter(|start| start.node >= nodes_count) {
+            issues.push(ValidationIssue::error(format!(
+                "first {} {} does not exist",
+                node, start.node
+            )));
+        }
+
+        for (node_index, next_choices) in transitions.iter().enumerate() {
+            for next in next_choices.iter() {
+                if next.target_music >= nodes_count {
+                    issues.push(ValidationIssue::error(format!(
+                        "{} goes to {} {} that does not exist",
+                        self.node_label(node_index),
+                        node,
+                        next.target_music
+                    )));
+                }
+
+                if next.wait.0.is_longer_than(&next.wait.1) {
+                    issues.push(ValidationIssue::error(format!(
+                        "{} goes to {} with a wait lower bound {} greater than the upper bound {}",
+                        self.node_label(node_index),
+                        self.node_label(next.target_music),
+                        next.wait.0,
+                        next.wait.1
+                    )));
+                }
+
+                if next.probability_weight == 0 {
+                    issues.push(ValidationIssue::error(format!(
+                        "{} goes to {} with a weight of 0",
+                        self.node_label(node_index),
+                        self.node_label(next.target_music),
+                    )));
+                }
+
+                if self.tempo.is_none()
+                    && (next.wait.0.unit == WaitUnit::Bars || next.wait.1.unit == WaitUnit::Bars)
+                {
+                    issues.push(ValidationIssue::error(format!(
+                        "{} goes to {} with a wait in bars but the feusic has no `bpm`",
+                        self.node_label(node_index),
+                        self.node_label(next.target_music),
+                    )));
+                }
+            }
+        }
+
+        let has_transitions = transitions.iter().any(|next| !next.is_empty());
+        let reachable = reachable_nodes(&self.start, &transitions);
+
+        for (node_index, next_choices) in transitions.iter().enumerate() {
+            if !reachable[node_index] {
+                issues.push(ValidationIssue::warning(format!(
+                    "{} is unreachable",
+                    self.node_label(node_index)
+                )));
+            } else if has_transitions && next_choices.is_empty() {
+                issues.push(ValidationIssue::error(format!(
+                    "{} has no transitions but the other {}s have",
+                    self.node_label(node_index),
+                    node
+                )));
+            }
+        }
+
+        if self.is_layered() {
+            for music_index in 0..self.musics.len() {
+                if self.states.iter().all(|s| s.layers[music_index].is_none()) {
+                    issues.push(ValidationIssue::warning(format!(
+                        "{} is not a layer of any state",
+                        self.music_label(music_index)
+                    )));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// [`Feusic::validate`] with the musics whose transitions the feusic.toml defines more
+    /// than once, only their first definition was loaded.
+    pub(super) fn validate_loaded(&self, duplicated_transitions: &[usize]) -> Vec<ValidationIssue> {
+        duplicated_transitions
+            .iter()
+            .map(|&music_index| {
+                ValidationIssue::error(format!(
+                    "{} has its transitions defined more than once",
+                    self.music_label(music_index)
+                ))
+            })
+            .chain(self.validate())
+            .collect()
+    }
+
+    fn node_label(&self, node_index: usize) -> String {
+        if !self.is_layered() {
+            return self.music_label(node_index);
+        }
+
+        match self.states.get(node_index) {
+            Some(state) => format!("state {} ({})", node_index, state.name),
+            None => format!("state {}", node_index),
+        }
+    }
+
+    fn music_label(&self, music_index: usize) -> String {
+        match self.musics.get(music_index) {
+            Some(music) => format!("music {} ({})", music_index, music.name),
+            None => format!("music {}", music_index),
+        }
+    }
+}
+
+fn reachable_nodes(start: &[Start], transitions: &[Vec<Next>]) -> Vec<bool> {
+    let mut reachable = vec![false; transitions.len()];
+    let mut to_visit = start.iter().map(|start| start.node).collect::<Vec<_>>();
+
+    while let Some(node_index) = to_visit.pop() {
+        match reachable.get_mut(node_index) {
+            Some(visited) if !*visited => *visited = true,
+            _ => continue,
+        }
+
+        to_visit.extend(transitions[node_index].iter().map(|next| next.target_music));
+    }
+
+    reachable
+}
+
+impl ValidationIssue {
+    pub fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    pub fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl Display for InvalidFeusicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid feusic.")?;
+        for issue in self.issues.iter() {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidFeusicError {}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::core::feusic::{
+        test_utils::{load, next, NUMBERED_MUSICS},
+        timing::Wait,
+        Fade, LayerState, Looping, Music,
+    };
+
+    use super::*;
+
+    fn feusic(first_music: usize, next_choices: Vec<Vec<(usize, (usize, usize))>>) -> Feusic<()> {
+        Feusic {
+            name: "feusic".to_string(),
+            start: vec![Start {
+                node: first_music,
+                probability_weight: 100,
+            }],
+            states: vec![],
+            looping: Looping::None,
+            tempo: None,
+            fade: Fade::default(),
+            meta: Default::default(),
+            cover: None,
+            intro: None,
+            stingers: vec![],
+            ending: None,
+            ending_fade: Duration::ZERO,
+            parameters: vec![],
+            musics: next_choices
+                .into_iter()
+                .enumerate()
+                .map(|(index, next_choices)| Music {
+                    name: format!("{}.mp3", index),
+                    title: None,
+                    loader: (),
+                    loop_region: None,
+                    gain_db: 0.0,
+                    ending: None,
+                    shuffle: false,
+                    next_choices: next_choices
+                        .into_iter()
+                        .map(|(target_music, wait)| next(target_music, wait))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn valid_feusic_has_no_issues() {
+        let feusic = feusic(0, vec![vec![(1, (10, 20))], vec![(0, (10, 20))]]);
+
+        assert_eq!(feusic.validate(), vec![]);
+    }
+
+    #[test]
+    fn reports_graph_issues() {
+        let feusic = feusic(
+            0,
+            vec![
+                vec![(1, (10, 20)), (2, (30, 20))],
+                vec![(5, (10, 20))],
+                vec![],
+                vec![(0, (10, 20))],
+            ],
+        );
+
+        assert_eq!(
+            feusic.validate(),
+            vec![
+                ValidationIssue::error("music 0 (0.mp3) goes to music 2 (2.mp3) with a wait lower bound 30 greater than the upper bound 20".to_string()),
+                ValidationIssue::error("music 1 (1.mp3) goes to music 5 that does not exist".to_string()),
+                ValidationIssue::error("music 2 (2.mp3) has no transitions but the other musics have".to_string()),
+                ValidationIssue::warning("music 3 (3.mp3) is unreachable".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_zero_weights() {
+        let mut feusic = feusic(0, vec![vec![(1, (10, 20))], vec![(0, (10, 20))]]);
+        feusic.musics[1].next_choices[0].probability_weight = 0;
+
+        assert_eq!(
+            feusic.validate(),
+            vec![ValidationIssue::error(
+                "music 1 (1.mp3) goes to music 0 (0.mp3) with a weight of 0".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_waits_in_bars_without_tempo() {
+        let mut feusic = feusic(0, vec![vec![(1, (10, 20))], vec![(0, (10, 20))]]);
+        feusic.musics[0].next_choices[0].wait.1 = Wait {
+            amount: 4,
+            unit: WaitUnit::Bars,
+        };
+
+        assert_eq!(
+            feusic.validate(),
+            vec![ValidationIssue::error(
+                "music 0 (0.mp3) goes to music 1 (1.mp3) with a wait in bars but the feusic has no `bpm`"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_first_music_out_of_range() {
+        let feusic = feusic(3, vec![vec![]]);
+
+        assert_eq!(
+            feusic.validate(),
+            vec![
+                ValidationIssue::error("first music 3 does not exist".to_string()),
+                ValidationIssue::warning("music 0 (0.mp3) is unreachable".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_layered_issues_on_states() {
+        let mut feusic = feusic(0, vec![vec![], vec![], vec![]]);
+        feusic.states = vec![
+            LayerState {
+                name: "explore".to_string(),
+                layers: vec![Some(0.0), None, None],
+                next_choices: vec![],
+                shuffle: false,
+            },
+            LayerState {
+                name: "combat".to_string(),
+                layers: vec![Some(0.0), Some(-3.0), None],
+                next_choices: vec![next(0, (10, 20))],
+                shuffle: false,
+            },
+        ];
+
+        assert_eq!(
+            feusic.validate(),
+            vec![
+                ValidationIssue::error(
+                    "state 0 (explore) has no transitions but the other states have".to_string()
+                ),
+                ValidationIssue::warning("state 1 (combat) is unreachable".to_string()),
+                ValidationIssue::warning("music 2 (2.mp3) is not a layer of any state".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicated_timing_entries_fail_to_load() {
+        let error = load(
+            &NUMBERED_MUSICS[..2],
+            r#"
+                timing = "s0|0:w10:1|1:w10:0|0:w20:1"
+                duration = 600
+            "#,
+        )
+        .unwrap_err();
+
+        let issues = &error.downcast_ref::<InvalidFeusicError>().unwrap().issues;
+        assert_eq!(
+            issues,
+            &vec![ValidationIssue::error(
+                "music 0 (0.mp3) has its transitions defined more than once".to_string()
+            )]
+        );
+    }
+}
@@ -1,4 +1,27 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether [`log!`] prints, the commands turn it off so only their own output is printed.
+static VERBOSE: AtomicBool = AtomicBool::new(true);
+
+/// `println!` for what the player and the loaders are doing, see [`set_verbose`].
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::core::is_verbose() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use log;
+
 pub mod feusic;
 pub mod player;
 pub mod playlist;
 pub mod youtube;
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
@@ -4,7 +4,7 @@ use std::{
     time::Duration,
 };
 
-use crate::core::feusic::{loader::MusicLoader, Feusic};
+use crate::core::feusic::{loader::MusicLoader, parameters::Parameter, Feusic, FeusicMeta};
 
 use super::{shared_data::SharedDataRef, FeusicPlayer, PlayerAction, PlayerSharedData};
 
@@ -72,6 +72,14 @@ impl<M: MusicLoader> FeusicPlayerController<M> {
         self.action_sender.send(PlayerAction::RemoveLoop).ok();
     }
 
+    /// Sets a parameter of the playing feusic, the transitions depending on it are checked
+    /// right away.
+    pub fn set_parameter(&self, name: &str, value: f64) {
+        self.action_sender
+            .send(PlayerAction::SetParameter(name.to_string(), value))
+            .ok();
+    }
+
     pub fn music_position(&self) -> Duration {
         self.shared_data.music_position()
     }
@@ -88,18 +96,42 @@ impl<M: MusicLoader> FeusicPlayerController<M> {
         self.shared_data.music_names()
     }
 
+    pub fn music_titles<'a>(&'a self) -> SharedDataRef<'a, Vec<String>> {
+        self.shared_data.music_titles()
+    }
+
     pub fn music_index(&self) -> usize {
         self.shared_data.music_index()
     }
 
+    pub fn cover(&self) -> Option<Arc<Vec<u8>>> {
+        self.shared_data.cover()
+    }
+
     pub fn feusic_names<'a>(&'a self) -> SharedDataRef<'a, Vec<String>> {
         self.shared_data.feusic_names()
     }
 
+    pub fn feusic_titles<'a>(&'a self) -> SharedDataRef<'a, Vec<String>> {
+        self.shared_data.feusic_titles()
+    }
+
+    pub fn feusic_metas<'a>(&'a self) -> SharedDataRef<'a, Vec<FeusicMeta>> {
+        self.shared_data.feusic_metas()
+    }
+
     pub fn feusic_index(&self) -> usize {
         self.shared_data.feusic_index()
     }
 
+    pub fn parameters<'a>(&'a self) -> SharedDataRef<'a, Vec<Parameter>> {
+        self.shared_data.parameters()
+    }
+
+    pub fn parameter_values<'a>(&'a self) -> SharedDataRef<'a, Vec<f64>> {
+        self.shared_data.parameter_values()
+    }
+
     fn run(&self, mut player: FeusicPlayer<M>) {
         thread::spawn(move || loop {
             player.tick();
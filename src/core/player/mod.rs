@@ -1,21 +1,25 @@
 pub mod controller;
 mod read_seek_source;
 pub mod shared_data;
-mod timer;
+pub mod timer;
 
+use kira::clock::{ClockHandle, ClockSpeed, ClockTime};
 use kira::sound::streaming::{StreamingSoundData, StreamingSoundHandle};
 use kira::sound::{FromFileError, PlaybackState};
 use kira::track::{TrackBuilder, TrackHandle};
 use kira::{AudioManager, AudioManagerSettings, Decibels, Easing, StartTime, Tween};
+use rand::rngs::StdRng;
+use rand::{rng, SeedableRng};
 use read_seek_source::ReadSeekSource;
 use shared_data::PlayerSharedData;
 use std::error::Error;
+use std::io::Read;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 use std::time::Duration;
-use timer::FeusicTimer;
+use timer::{FeusicTimer, InstantClock, TimerEvent};
 
-use crate::core::feusic::Looping;
+use crate::core::feusic::{Fade, FadeEasing, Looping, Stinger};
 
 use super::feusic::loader::MusicLoader;
 use super::feusic::Feusic;
@@ -34,11 +38,20 @@ pub struct FeusicPlayer<M: MusicLoader> {
 
     audio_manager: AudioManager,
     musics: Vec<(TrackHandle, StreamingSoundHandle<FromFileError>)>,
+    /// Durations of the loaded musics, the waits in loops are resolved with them.
+    musics_durations: Vec<Duration>,
     current_music_index: usize,
+    intro: Option<ClipPlayback>,
+    /// Clock started with the intro, the musics start on its tick at the end of the intro.
+    intro_clock: Option<ClockHandle>,
+    ending: Option<ClipPlayback>,
+    stingers: Vec<StreamingSoundHandle<FromFileError>>,
+    /// Values of the parameters of the playing feusic.
+    parameters: Vec<f64>,
 
     pub(super) action_sender: Sender<PlayerAction<M>>,
     action_receiver: Receiver<PlayerAction<M>>,
-    timer: FeusicTimer<M>,
+    timer: FeusicTimer,
 
     shared_data: Arc<PlayerSharedData>,
 }
@@ -49,6 +62,12 @@ const INSTANT_TWEEN: Tween = Tween {
     start_time: StartTime::Immediate,
 };
 
+/// Resolution of the clock of the intro.
+const INTRO_CLOCK_TICKS_PER_SECOND: f64 = 1000.0;
+
+/// How fast the musics fading out are ducked when a stinger starts.
+const STINGER_DUCK_DURATION: Duration = Duration::from_millis(50);
+
 #[derive(Debug)]
 pub(super) enum PlayerAction<M: MusicLoader> {
     Play,
@@ -58,12 +77,31 @@ pub(super) enum PlayerAction<M: MusicLoader> {
     Stop,
     Next,
     CrossfadeNext(Duration),
-    CrossfadeWith(Duration, usize),
+    CrossfadeWith(Crossfade),
     Seek(Duration),
     RemoveLoop,
+    /// The feusic duration expired, sent by the timer with the delay to the next bar or beat.
+    End(Duration),
+    SetParameter(String, f64),
     SetPlaylist(Vec<Feusic<M>>),
 }
 
+/// Intro or ending of the playing feusic, kept until it finishes.
+struct ClipPlayback {
+    _track: TrackHandle,
+    handle: StreamingSoundHandle<FromFileError>,
+    duration: Duration,
+}
+
+/// Crossfade from the current music to `target_music`, starting after `delay`.
+#[derive(Debug)]
+pub struct Crossfade {
+    pub target_music: usize,
+    pub fade: Fade,
+    pub delay: Duration,
+    pub stinger: Option<Stinger>,
+}
+
 impl<M: MusicLoader> FeusicPlayer<M> {
     pub fn new() -> Result<FeusicPlayer<M>, Box<dyn std::error::Error>> {
         let (action_sender, action_receiver) = mpsc::channel();
@@ -75,11 +113,17 @@ impl<M: MusicLoader> FeusicPlayer<M> {
             current_feusic_index: 0,
             action_sender: action_sender.clone(),
             action_receiver,
-            timer: FeusicTimer::new(action_sender.clone(), 0, None, vec![]),
+            timer: FeusicTimer::new(InstantClock::default(), StdRng::from_rng(&mut rng())),
             state: PlayerState::Stopped,
 
             audio_manager: manager,
             musics: vec![],
+            musics_durations: vec![],
+            intro: None,
+            intro_clock: None,
+            ending: None,
+            stingers: vec![],
+            parameters: vec![],
             shared_data: Arc::new(PlayerSharedData::default()),
         })
     }
@@ -89,6 +133,13 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         self.feusics = playlist;
         *self.shared_data.feusic_names.write().unwrap() =
             self.feusics.iter().map(|f| f.name.clone()).collect();
+        *self.shared_data.feusic_titles.write().unwrap() = self
+            .feusics
+            .iter()
+            .map(|f| f.display_name().to_string())
+            .collect();
+        *self.shared_data.feusic_metas.write().unwrap() =
+            self.feusics.iter().map(|f| f.meta.clone()).collect();
     }
 
     fn reset(&mut self) {
@@ -99,34 +150,45 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         self.timer.stop();
         self.state = PlayerState::Stopped;
         self.musics.drain(..);
+        self.intro = None;
+        self.intro_clock = None;
+        self.ending = None;
+        self.stop_stingers();
     }
 
     fn play_feusic(&mut self, feusic_index: usize) -> Result<(), Box<dyn Error>> {
         self.musics.drain(..);
+        self.intro = None;
+        self.intro_clock = None;
+        self.ending = None;
+        self.stop_stingers();
 
-        self.set_current_music_index(self.feusics[feusic_index].first_music);
+        self.set_current_music_index(self.feusics[feusic_index].choose_start(&mut rng()));
         self.set_current_feusic_index(feusic_index);
 
         let feusic = &self.feusics[feusic_index];
         let mut tracks = Vec::new();
+        let mut musics_durations = Vec::new();
         let mut feusic_duration = Duration::from_secs(0);
         for music in &self.feusics[feusic_index].musics {
-            let mut track = self.audio_manager.add_sub_track(TrackBuilder::default())?;
-            let loaded_music = music.loader.read()?;
-            let media_source = ReadSeekSource::new(loaded_music.reader);
-            let sound_data = StreamingSoundData::from_media_source(media_source)
-                .map_err(|e| format!("When getting streaming sound data: {}", e))?;
-            feusic_duration = sound_data.duration();
-
-            let mut handle = track.play(sound_data)?;
-            match &feusic.looping {
-                Looping::Partial { start, end, .. } => {
+            let (track, mut handle, duration) = play_on_sub_track(
+                &mut self.audio_manager,
+                streaming_sound_data(&music.loader)?,
+            )?;
+            feusic_duration = duration;
+            musics_durations.push(duration);
+
+            match (&music.loop_region, &feusic.looping) {
+                (_, Looping::None) => {}
+                (Some(region), _) => {
+                    handle.set_loop_region(region.start..region.end);
+                }
+                (None, Looping::Partial { start, end, .. }) => {
                     handle.set_loop_region(*start..*end);
                 }
-                Looping::Whole(_) => {
+                (None, Looping::Whole(_)) => {
                     handle.set_loop_region(..);
                 }
-                _ => {}
             }
             handle.pause(INSTANT_TWEEN);
 
@@ -134,22 +196,49 @@ impl<M: MusicLoader> FeusicPlayer<M> {
             println!("Loaded audio file: {}", music.name);
         }
 
+        if let Some(intro) = &feusic.intro {
+            let (track, mut handle, duration) = play_on_sub_track(
+                &mut self.audio_manager,
+                streaming_sound_data(&intro.loader)?,
+            )?;
+            handle.pause(INSTANT_TWEEN);
+
+            self.intro = Some(ClipPlayback {
+                _track: track,
+                handle,
+                duration,
+            });
+            self.intro_clock = Some(
+                self.audio_manager
+                    .add_clock(ClockSpeed::TicksPerSecond(INTRO_CLOCK_TICKS_PER_SECOND))?,
+            );
+            println!("Loaded intro: {}", intro.name);
+        }
+
         self.musics = tracks;
-        self.timer.reset(
-            self.current_music_index,
-            feusic.looping.duration(),
-            feusic
-                .musics
-                .iter()
-                .map(|m| m.next_choices.clone())
-                .collect::<Vec<_>>(),
-        );
+        self.musics_durations = musics_durations;
         *self.shared_data.music_names.write().unwrap() =
             feusic.musics.iter().map(|m| m.name.clone()).collect();
+        *self.shared_data.music_titles.write().unwrap() = feusic.node_titles();
+        self.parameters = feusic.parameters.iter().map(|p| p.default).collect();
+        *self.shared_data.parameters.write().unwrap() = feusic.parameters.clone();
+        *self.shared_data.parameter_values.write().unwrap() = self.parameters.clone();
+        *self.shared_data.cover.write().unwrap() = feusic.cover.as_ref().and_then(|cover| {
+            read_cover(cover)
+                .inspect_err(|e| eprintln!("Error reading cover of {}: {}", feusic.name, e))
+                .ok()
+        });
         self.shared_data.feusic_duration_in_secs.store(
             feusic_duration.as_secs() as usize,
             std::sync::atomic::Ordering::Relaxed,
         );
+
+        if self.intro.is_some() {
+            // The timer starts when the intro ends.
+            self.timer.stop();
+        } else {
+            self.reset_timer();
+        }
         self.play_internal();
 
         Ok(())
@@ -176,21 +265,73 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         Ok(())
     }
 
+    fn reset_timer(&mut self) {
+        let feusic = &self.feusics[self.current_feusic_index];
+        self.timer.reset(
+            self.current_music_index,
+            feusic.looping.duration(),
+            feusic.resolved_transitions(&self.musics_durations),
+            feusic.tempo,
+            self.parameters.clone(),
+            feusic.shuffled_nodes(),
+        );
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f64) {
+        let Some(feusic) = self.feusics.get(self.current_feusic_index) else {
+            return;
+        };
+        let Some(index) = feusic.parameters.iter().position(|p| p.name == name) else {
+            eprintln!("{} has no parameter named {}", feusic.name, name);
+            return;
+        };
+
+        let parameter = &feusic.parameters[index];
+        let value = value.clamp(parameter.min, parameter.max);
+        println!("Set parameter {} to {}", name, value);
+
+        self.parameters[index] = value;
+        self.shared_data.parameter_values.write().unwrap()[index] = value;
+        self.timer.set_parameter(index, value);
+    }
+
     fn play_internal(&mut self) {
+        // The intro and the musics are scheduled on the same clock, so the musics start on
+        // the sample the intro ends, even after a pause.
+        let musics_start_time = match (&mut self.intro, &mut self.intro_clock) {
+            (Some(intro), Some(clock)) => {
+                intro.handle.resume(Tween {
+                    start_time: StartTime::ClockTime(clock.time()),
+                    ..INSTANT_TWEEN
+                });
+                clock.start();
+                StartTime::ClockTime(clock_time_at(clock, intro.duration))
+            }
+            _ => StartTime::Immediate,
+        };
+
+        let feusic = &self.feusics[self.current_feusic_index];
         for (i, (_, handle)) in self.musics.iter_mut().enumerate() {
-            if self.current_music_index == i {
-                println!(
-                    "Play audio {} at volume 1",
-                    self.feusics[self.current_feusic_index].musics[i].name
-                );
-                handle.set_volume(Decibels::IDENTITY, INSTANT_TWEEN);
-            } else {
-                println!(
-                    "Play audio {} at volume 0",
-                    self.feusics[self.current_feusic_index].musics[i].name
-                );
-                handle.set_volume(Decibels::SILENCE, INSTANT_TWEEN);
+            let music = &feusic.musics[i];
+            match feusic.music_volume(self.current_music_index, i) {
+                Some(volume) => {
+                    println!("Play audio {} at {} dB", music.name, volume);
+                    handle.set_volume(Decibels(volume as f32), INSTANT_TWEEN);
+                }
+                None => {
+                    println!("Play audio {} at volume 0", music.name);
+                    handle.set_volume(Decibels::SILENCE, INSTANT_TWEEN);
+                }
             }
+            handle.resume(Tween {
+                start_time: musics_start_time,
+                ..INSTANT_TWEEN
+            });
+        }
+        if let Some(ending) = &mut self.ending {
+            ending.handle.resume(INSTANT_TWEEN);
+        }
+        for handle in self.stingers.iter_mut() {
             handle.resume(INSTANT_TWEEN);
         }
         self.state = PlayerState::Playing;
@@ -210,10 +351,66 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         println!("Removed loop");
     }
 
+    /// Crossfades or cuts into the ending of the current music or feusic after `delay`,
+    /// removes the loop when there is no ending. The feusic ends with its ending.
+    fn end(&mut self, delay: Duration) -> Result<(), Box<dyn Error>> {
+        let feusic = &self.feusics[self.current_feusic_index];
+        let Some(ending) = feusic.ending_of(self.current_music_index) else {
+            self.remove_loop();
+            return Ok(());
+        };
+
+        let (fade_in_easing, fade_out_easing) = fade_easings(feusic.fade.easing);
+        let start_time = if delay.is_zero() {
+            StartTime::Immediate
+        } else {
+            StartTime::Delayed(delay)
+        };
+        let fade_in_tween = (!feusic.ending_fade.is_zero()).then_some(Tween {
+            duration: feusic.ending_fade,
+            easing: fade_in_easing,
+            start_time: StartTime::Immediate,
+        });
+
+        let sound_data = streaming_sound_data(&ending.loader)?
+            .start_time(start_time)
+            .fade_in_tween(fade_in_tween);
+        let (track, handle, duration) = play_on_sub_track(&mut self.audio_manager, sound_data)?;
+        println!("Ending with {}", ending.name);
+
+        for (_, handle) in self.musics.iter_mut() {
+            handle.stop(Tween {
+                duration: feusic.ending_fade,
+                easing: fade_out_easing,
+                start_time,
+            });
+        }
+
+        self.ending = Some(ClipPlayback {
+            _track: track,
+            handle,
+            duration,
+        });
+
+        Ok(())
+    }
+
     fn pause(&mut self) {
         for (_, handle) in self.musics.iter_mut() {
             handle.pause(INSTANT_TWEEN);
         }
+        if let Some(intro) = &mut self.intro {
+            intro.handle.pause(INSTANT_TWEEN);
+        }
+        if let Some(clock) = &mut self.intro_clock {
+            clock.pause();
+        }
+        if let Some(ending) = &mut self.ending {
+            ending.handle.pause(INSTANT_TWEEN);
+        }
+        for handle in self.stingers.iter_mut() {
+            handle.pause(INSTANT_TWEEN);
+        }
         println!("Paused audio.");
         self.state = PlayerState::Paused;
     }
@@ -226,6 +423,14 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         for (_, handle) in self.musics.iter_mut() {
             handle.stop(INSTANT_TWEEN);
         }
+        if let Some(mut intro) = self.intro.take() {
+            intro.handle.stop(INSTANT_TWEEN);
+        }
+        self.intro_clock = None;
+        if let Some(mut ending) = self.ending.take() {
+            ending.handle.stop(INSTANT_TWEEN);
+        }
+        self.stop_stingers();
         println!("Stopped audio.");
         self.state = PlayerState::Stopped;
     }
@@ -249,15 +454,27 @@ impl<M: MusicLoader> FeusicPlayer<M> {
             println!("Attempted to crossfade with no feusic playing");
             Ok(())
         } else {
-            self.crossfade_with(duration, (self.current_music_index + 1) % self.musics.len())
+            let nodes_count = self.feusics[self.current_feusic_index].transitions().len();
+            self.crossfade_with(Crossfade {
+                target_music: (self.current_music_index + 1) % nodes_count,
+                fade: Fade {
+                    duration,
+                    easing: FadeEasing::default(),
+                },
+                delay: Duration::ZERO,
+                stinger: None,
+            })
         }
     }
 
-    fn crossfade_with(
-        &mut self,
-        duration: Duration,
-        next_music_index: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    fn crossfade_with(&mut self, crossfade: Crossfade) -> Result<(), Box<dyn Error>> {
+        let Crossfade {
+            target_music: next_music_index,
+            fade,
+            delay,
+            stinger,
+        } = crossfade;
+
         match self.state {
             PlayerState::Paused => {
                 println!("Not crossfading, paused");
@@ -266,43 +483,85 @@ impl<M: MusicLoader> FeusicPlayer<M> {
                 println!("Not crossfading, stopped");
             }
             PlayerState::Playing => {
-                if next_music_index >= self.musics.len() {
+                let feusic = &self.feusics[self.current_feusic_index];
+                let nodes_count = feusic.transitions().len();
+                if next_music_index >= nodes_count {
                     eprintln!("Target music index {} does not exists.", next_music_index);
                     return Ok(());
                 }
 
-                if self.musics.len() < 2 {
+                if nodes_count < 2 {
                     println!("Crossfade requires at least two audio files in a feusic.");
                     return Ok(());
                 }
 
-                self.musics
-                    .get_mut(next_music_index)
-                    .map(|(_, next_handle)| {
-                        next_handle.set_volume(
-                            Decibels::IDENTITY,
+                let (fade_in_easing, fade_out_easing) = fade_easings(fade.easing);
+                let start_time = if delay.is_zero() {
+                    StartTime::Immediate
+                } else {
+                    StartTime::Delayed(delay)
+                };
+
+                // Every music fades from its volume in the current music or state to its
+                // volume in the next one, in layered mode more than one can be playing.
+                // Ducking lowers the track of a music, its volume in the feusic is untouched.
+                let duck_db = stinger.map(|stinger| stinger.duck_db).unwrap_or(0.0);
+                for (i, (track, handle)) in self.musics.iter_mut().enumerate() {
+                    let current_volume = feusic.music_volume(self.current_music_index, i);
+                    let next_volume = feusic.music_volume(next_music_index, i);
+
+                    if duck_db != 0.0
+                        && feusic.is_ducked_by_stinger(
+                            self.current_music_index,
+                            next_music_index,
+                            i,
+                        )
+                    {
+                        track.set_volume(
+                            Decibels(duck_db as f32),
                             Tween {
-                                duration,
-                                easing: Easing::InPowf(0.15),
-                                ..Default::default()
+                                duration: STINGER_DUCK_DURATION,
+                                easing: Easing::Linear,
+                                start_time,
                             },
-                        )
-                    });
-                self.musics
-                    .get_mut(self.current_music_index)
-                    .map(|(_, current_handle)| {
-                        current_handle.set_volume(
-                            Decibels::SILENCE,
+                        );
+                    } else if next_volume.is_some() {
+                        track.set_volume(
+                            Decibels::IDENTITY,
                             Tween {
-                                duration,
-                                easing: Easing::OutPowf(0.15),
-                                ..Default::default()
+                                duration: fade.duration,
+                                easing: fade_in_easing,
+                                start_time,
                             },
-                        )
-                    });
+                        );
+                    }
+
+                    if current_volume == next_volume {
+                        continue;
+                    }
+
+                    handle.set_volume(
+                        next_volume
+                            .map(|volume| Decibels(volume as f32))
+                            .unwrap_or(Decibels::SILENCE),
+                        Tween {
+                            duration: fade.duration,
+                            easing: if next_volume > current_volume {
+                                fade_in_easing
+                            } else {
+                                fade_out_easing
+                            },
+                            start_time,
+                        },
+                    );
+                }
 
                 println!("Crossfade");
 
+                if let Some(stinger) = stinger {
+                    self.play_stinger(stinger, start_time)?;
+                }
+
                 self.set_current_music_index(next_music_index);
             }
         }
@@ -310,6 +569,31 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         Ok(())
     }
 
+    fn play_stinger(
+        &mut self,
+        stinger: Stinger,
+        start_time: StartTime,
+    ) -> Result<(), Box<dyn Error>> {
+        let clip = self.feusics[self.current_feusic_index]
+            .stingers
+            .get(stinger.clip)
+            .ok_or_else(|| format!("Stinger {} does not exist", stinger.clip))?;
+        let sound_data = streaming_sound_data(&clip.loader)?.start_time(start_time);
+
+        self.stingers
+            .retain(|handle| !matches!(handle.state(), PlaybackState::Stopped));
+        self.stingers.push(self.audio_manager.play(sound_data)?);
+        println!("Stinger {}", clip.name);
+
+        Ok(())
+    }
+
+    fn stop_stingers(&mut self) {
+        for mut handle in self.stingers.drain(..) {
+            handle.stop(INSTANT_TWEEN);
+        }
+    }
+
     fn set_current_music_index(&mut self, index: usize) {
         self.current_music_index = index;
         self.shared_data
@@ -335,16 +619,41 @@ impl<M: MusicLoader> FeusicPlayer<M> {
             .is_paused
             .store(is_paused, std::sync::atomic::Ordering::Relaxed);
 
+        if self
+            .intro
+            .as_ref()
+            .is_some_and(|intro| matches!(intro.handle.state(), PlaybackState::Stopped))
+        {
+            println!("Intro ended");
+            self.intro = None;
+            self.intro_clock = None;
+            self.reset_timer();
+        }
+
         if !is_paused {
-            self.timer.tick();
+            match self.timer.tick(self.audible_music_position()) {
+                Some(TimerEvent::Transition(crossfade)) => {
+                    self.action_sender
+                        .send(PlayerAction::CrossfadeWith(crossfade))
+                        .unwrap();
+                }
+                Some(TimerEvent::End(delay)) => {
+                    self.action_sender.send(PlayerAction::End(delay)).unwrap();
+                }
+                None => {}
+            }
         }
 
-        if self
-            .musics
-            .get(0)
-            .map(|(_, handle)| matches!(handle.state(), PlaybackState::Stopped))
-            .unwrap_or(false)
-        {
+        // A feusic that ends with an ending is over when the ending is, not its musics.
+        let is_over = match &self.ending {
+            Some(ending) => matches!(ending.handle.state(), PlaybackState::Stopped),
+            None => self
+                .musics
+                .get(0)
+                .map(|(_, handle)| matches!(handle.state(), PlaybackState::Stopped))
+                .unwrap_or(false),
+        };
+        if is_over {
             self.action_sender.send(PlayerAction::Next).ok();
         }
 
@@ -385,8 +694,9 @@ impl<M: MusicLoader> FeusicPlayer<M> {
                         eprintln!("Error crossfading next: {}", e);
                     }
                 }
-                PlayerAction::CrossfadeWith(duration, index) => {
-                    if let Err(e) = self.crossfade_with(duration, index) {
+                PlayerAction::CrossfadeWith(crossfade) => {
+                    let index = crossfade.target_music;
+                    if let Err(e) = self.crossfade_with(crossfade) {
                         eprintln!("Error crossfading index {}: {}", index, e);
                     }
                 }
@@ -396,6 +706,15 @@ impl<M: MusicLoader> FeusicPlayer<M> {
                 PlayerAction::RemoveLoop => {
                     self.remove_loop();
                 }
+                PlayerAction::SetParameter(name, value) => {
+                    self.set_parameter(&name, value);
+                }
+                PlayerAction::End(delay) => {
+                    if let Err(e) = self.end(delay) {
+                        eprintln!("Error playing the ending: {}", e);
+                        self.remove_loop();
+                    }
+                }
                 PlayerAction::SetPlaylist(playlist) => {
                     self.set_playlist(playlist);
                 }
@@ -410,6 +729,19 @@ impl<M: MusicLoader> FeusicPlayer<M> {
             .unwrap_or(Duration::from_secs(0))
     }
 
+    fn audible_music_position(&self) -> Duration {
+        // The layers of a layered feusic play in sync, the first one tells the position.
+        let audible_music_index = match self.feusics.get(self.current_feusic_index) {
+            Some(feusic) if feusic.is_layered() => 0,
+            _ => self.current_music_index,
+        };
+
+        self.musics
+            .get(audible_music_index)
+            .map(|(_, handle)| Duration::from_secs_f64(handle.position()))
+            .unwrap_or(Duration::from_secs(0))
+    }
+
     pub fn paused(&self) -> bool {
         matches!(self.state, PlayerState::Paused | PlayerState::Stopped)
     }
@@ -418,3 +750,50 @@ impl<M: MusicLoader> FeusicPlayer<M> {
         self.shared_data.clone()
     }
 }
+
+/// Time of `clock` when it has been running for `time`, the clocks start at tick 0.
+fn clock_time_at(clock: &ClockHandle, time: Duration) -> ClockTime {
+    let ticks = time.as_secs_f64() * INTRO_CLOCK_TICKS_PER_SECOND;
+    ClockTime {
+        clock: clock.id(),
+        ticks: ticks.trunc() as u64,
+        fraction: ticks.fract(),
+    }
+}
+
+/// Starts streaming an audio file of a feusic on a new sub track.
+fn play_on_sub_track(
+    audio_manager: &mut AudioManager,
+    sound_data: StreamingSoundData<FromFileError>,
+) -> Result<(TrackHandle, StreamingSoundHandle<FromFileError>, Duration), Box<dyn Error>> {
+    let mut track = audio_manager.add_sub_track(TrackBuilder::default())?;
+    let duration = sound_data.duration();
+
+    let handle = track.play(sound_data)?;
+
+    Ok((track, handle, duration))
+}
+
+fn streaming_sound_data<M: MusicLoader>(
+    loader: &M,
+) -> Result<StreamingSoundData<FromFileError>, Box<dyn Error>> {
+    let loaded_music = loader.read()?;
+    let media_source = ReadSeekSource::new(loaded_music.reader);
+
+    Ok(StreamingSoundData::from_media_source(media_source)
+        .map_err(|e| format!("When getting streaming sound data: {}", e))?)
+}
+
+fn read_cover<M: MusicLoader>(cover: &M) -> Result<Arc<Vec<u8>>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    cover.read()?.reader.read_to_end(&mut bytes)?;
+    Ok(Arc::new(bytes))
+}
+
+/// Easings of the music fading in and of the music fading out.
+fn fade_easings(easing: FadeEasing) -> (Easing, Easing) {
+    match easing {
+        FadeEasing::Linear => (Easing::Linear, Easing::Linear),
+        FadeEasing::Power(power) => (Easing::InPowf(power), Easing::OutPowf(power)),
+    }
+}
@@ -1,19 +1,27 @@
 use std::{
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        RwLock, RwLockReadGuard,
+        Arc, RwLock, RwLockReadGuard,
     },
     time::Duration,
 };
 
+use crate::core::feusic::{parameters::Parameter, FeusicMeta};
+
 pub struct PlayerSharedData {
     pub(super) feusic_duration_in_secs: AtomicUsize,
     pub(super) feusic_position_in_secs: AtomicUsize,
     pub(super) is_paused: AtomicBool,
     pub(super) feusic_names: RwLock<Vec<String>>,
+    pub(super) feusic_titles: RwLock<Vec<String>>,
+    pub(super) feusic_metas: RwLock<Vec<FeusicMeta>>,
     pub(super) feusic_index: AtomicUsize,
     pub(super) music_names: RwLock<Vec<String>>,
+    pub(super) music_titles: RwLock<Vec<String>>,
     pub(super) music_index: AtomicUsize,
+    pub(super) cover: RwLock<Option<Arc<Vec<u8>>>>,
+    pub(super) parameters: RwLock<Vec<Parameter>>,
+    pub(super) parameter_values: RwLock<Vec<f64>>,
 }
 
 impl Default for PlayerSharedData {
@@ -23,9 +31,15 @@ impl Default for PlayerSharedData {
             feusic_duration_in_secs: Default::default(),
             feusic_position_in_secs: Default::default(),
             feusic_names: Default::default(),
+            feusic_titles: Default::default(),
+            feusic_metas: Default::default(),
             feusic_index: Default::default(),
             music_names: Default::default(),
+            music_titles: Default::default(),
             music_index: Default::default(),
+            cover: Default::default(),
+            parameters: Default::default(),
+            parameter_values: Default::default(),
         }
     }
 }
@@ -49,6 +63,19 @@ impl PlayerSharedData {
         }
     }
 
+    /// Titles of the feusics in the playlist, their names when they have no title.
+    pub fn feusic_titles<'a>(&'a self) -> SharedDataRef<'a, Vec<String>> {
+        SharedDataRef {
+            guard: self.feusic_titles.read().unwrap(),
+        }
+    }
+
+    pub fn feusic_metas<'a>(&'a self) -> SharedDataRef<'a, Vec<FeusicMeta>> {
+        SharedDataRef {
+            guard: self.feusic_metas.read().unwrap(),
+        }
+    }
+
     pub fn feusic_index(&self) -> usize {
         self.feusic_index.load(Ordering::Relaxed)
     }
@@ -59,18 +86,51 @@ impl PlayerSharedData {
         }
     }
 
+    /// Titles of the musics of the playing feusic, their names when they have no title.
+    /// The names of the states for a layered feusic.
+    pub fn music_titles<'a>(&'a self) -> SharedDataRef<'a, Vec<String>> {
+        SharedDataRef {
+            guard: self.music_titles.read().unwrap(),
+        }
+    }
+
     pub fn music_index(&self) -> usize {
         self.music_index.load(Ordering::Relaxed)
     }
 
+    /// Bytes of the cover image of the playing feusic.
+    pub fn cover(&self) -> Option<Arc<Vec<u8>>> {
+        self.cover.read().unwrap().clone()
+    }
+
+    /// Parameters of the playing feusic.
+    pub fn parameters<'a>(&'a self) -> SharedDataRef<'a, Vec<Parameter>> {
+        SharedDataRef {
+            guard: self.parameters.read().unwrap(),
+        }
+    }
+
+    /// Current values of the parameters of the playing feusic, in the same order.
+    pub fn parameter_values<'a>(&'a self) -> SharedDataRef<'a, Vec<f64>> {
+        SharedDataRef {
+            guard: self.parameter_values.read().unwrap(),
+        }
+    }
+
     pub(super) fn reset(&self) {
         self.feusic_duration_in_secs.store(0, Ordering::Relaxed);
         self.feusic_position_in_secs.store(0, Ordering::Relaxed);
         self.feusic_names.write().unwrap().clear();
+        self.feusic_titles.write().unwrap().clear();
+        self.feusic_metas.write().unwrap().clear();
         self.feusic_index.store(0, Ordering::Relaxed);
         self.is_paused.store(true, Ordering::Relaxed);
         self.music_names.write().unwrap().clear();
+        self.music_titles.write().unwrap().clear();
         self.music_index.store(0, Ordering::Relaxed);
+        *self.cover.write().unwrap() = None;
+        self.parameters.write().unwrap().clear();
+        self.parameter_values.write().unwrap().clear();
     }
 }
 
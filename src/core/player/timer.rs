@@ -1,160 +1,372 @@
-use std::{
-    sync::mpsc::Sender,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
-use rand::{rng, Rng};
+use rand::rngs::StdRng;
 
 use crate::core::{
-    feusic::{loader::MusicLoader, Next},
-    player::PlayerAction,
+    feusic::{selection::TransitionHistory, tempo::Tempo, Next},
+    player::Crossfade,
 };
 
-pub struct FeusicTimer<M: MusicLoader> {
+/// Time the [`FeusicTimer`] is measured with.
+pub trait Clock {
+    /// Time since the clock was created, it never goes back.
+    fn now(&self) -> Duration;
+}
+
+/// Clock of the player, the real time.
+pub struct InstantClock(Instant);
+
+/// Clock of the simulations, it only moves when it is set.
+#[derive(Default)]
+pub struct VirtualClock {
+    pub now: Duration,
+}
+
+/// What the timer asks the player to do.
+#[derive(Debug)]
+pub enum TimerEvent {
+    /// The wait of a transition is over, crossfade to its target.
+    Transition(Crossfade),
+    /// The feusic duration expired, with the delay to the next bar or beat.
+    End(Duration),
+}
+
+pub struct FeusicTimer<C: Clock = InstantClock> {
+    /// Transitions of every node with their waits in milliseconds, see
+    /// [`crate::core::feusic::Feusic::resolved_transitions`].
     timings: Vec<Vec<Next>>,
     timing_index: usize,
-    case_index: usize,
-    sender: Sender<PlayerAction<M>>,
-    change_time: Instant,
+    /// Transition waiting to happen, `None` when the conditions of all of them are false.
+    case_index: Option<usize>,
+    /// When the current music or state started, the waits of its transitions count from it.
+    entered: Duration,
+    clock: C,
+    rng: StdRng,
+    change_time: Duration,
     running: bool,
-    last_tick: Instant,
-    time_left_secs: Option<f32>,
+    last_tick: Duration,
+    time_left: Option<Duration>,
+    tempo: Option<Tempo>,
+    /// Values of the feusic parameters the conditions of the transitions are checked with.
+    parameters: Vec<f64>,
+    /// Transitions taken since the feusic started, to respect their cooldowns and shuffle bags.
+    history: TransitionHistory,
+    started: Duration,
 }
 
-const CROSSFADE_TIME_FIRE_EMBLEM: Duration = Duration::from_millis(1000);
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
 
-impl<M: MusicLoader> FeusicTimer<M> {
-    pub fn new(
-        sender: Sender<PlayerAction<M>>,
-        start: usize,
-        duration: Option<Duration>,
-        timings: Vec<Vec<Next>>,
-    ) -> Self {
-        println!("New timer");
-
-        let running = has_timings(&timings);
-
-        if running {
-            let mut timer = Self {
-                sender,
-                timing_index: start,
-                case_index: Self::find_next_case_index(&timings[start]),
-                running,
-                timings,
-                change_time: Instant::now(),
-                last_tick: Instant::now(),
-                time_left_secs: duration.map(|duration| duration.as_secs_f32()),
-            };
-
-            timer.wait_until_next_change();
-            timer
-        } else {
-            Self {
-                sender,
-                timing_index: 0,
-                case_index: 0,
-                running: false,
-                timings: vec![],
-                change_time: Instant::now(),
-                last_tick: Instant::now(),
-                time_left_secs: None,
-            }
+impl Clock for InstantClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+}
+
+impl<C: Clock> FeusicTimer<C> {
+    /// Stopped timer, it starts with [`FeusicTimer::reset`]. The transitions are chosen with
+    /// `rng`.
+    pub fn new(clock: C, rng: StdRng) -> Self {
+        log!("New timer");
+
+        Self {
+            timing_index: 0,
+            case_index: None,
+            running: false,
+            timings: vec![],
+            change_time: clock.now(),
+            entered: clock.now(),
+            last_tick: clock.now(),
+            time_left: None,
+            tempo: None,
+            parameters: vec![],
+            history: TransitionHistory::default(),
+            started: clock.now(),
+            clock,
+            rng,
         }
     }
 
-    pub fn reset(&mut self, start: usize, duration: Option<Duration>, timings: Vec<Vec<Next>>) {
-        println!("Reset timer");
+    pub fn reset(
+        &mut self,
+        start: usize,
+        duration: Option<Duration>,
+        timings: Vec<Vec<Next>>,
+        tempo: Option<Tempo>,
+        parameters: Vec<f64>,
+        shuffled: Vec<bool>,
+    ) {
+        log!("Reset timer");
 
-        self.running = has_timings(&timings);
+        // Without transitions the timer still counts down the duration to end the feusic.
+        self.running = has_timings(&timings) || duration.is_some();
+        self.parameters = parameters;
 
         if self.running {
-            self.case_index = Self::find_next_case_index(&timings[start]);
             self.timing_index = start;
             self.timings = timings;
-            self.change_time = Instant::now();
-            self.last_tick = Instant::now();
-            self.time_left_secs = duration.map(|duration| duration.as_secs_f32());
+            self.change_time = self.clock.now();
+            self.last_tick = self.clock.now();
+            self.time_left = duration;
+            self.tempo = tempo;
+            self.history = TransitionHistory::new(shuffled);
+            self.started = self.clock.now();
 
-            self.wait_until_next_change();
+            self.history.enter(start, Duration::ZERO);
+            self.wait_until_next_change(self.clock.now());
         }
     }
 
-    pub fn tick(&mut self) {
+    /// Moves the timer forward, `position` is the playback position of the audible music
+    /// and is used to quantize the transitions to the tempo.
+    pub fn tick(&mut self, position: Duration) -> Option<TimerEvent> {
         if !self.running {
-            return;
+            return None;
         }
 
-        let new_tick = Instant::now();
-        let delta_as_secs = (new_tick - self.last_tick).as_secs_f32();
+        let new_tick = self.clock.now();
+        let delta = new_tick.saturating_sub(self.last_tick);
 
-        self.time_left_secs = self
-            .time_left_secs
-            .map(|time_left| time_left - delta_as_secs);
+        self.time_left = self
+            .time_left
+            .map(|time_left| time_left.saturating_sub(delta));
         self.last_tick = new_tick;
 
-        match self.time_left_secs {
-            Some(time_left_secs) if time_left_secs <= 0.0 => {
-                println!("TIMING:remove_loop");
-                self.sender.send(PlayerAction::RemoveLoop).unwrap();
-                self.running = false;
-                return;
-            }
-            _ => {}
+        if self.time_left == Some(Duration::ZERO) {
+            let delay = self.quantized_delay(position);
+            log!("TIMING:end:delay:{:?}", delay);
+            self.running = false;
+            return Some(TimerEvent::End(delay));
         }
 
-        if Instant::now() < self.change_time {
-            return;
+        let case_index = self.case_index?;
+
+        if self.clock.now() < self.change_time {
+            return None;
         }
 
-        let current = &self.timings[self.timing_index];
-        let case = &current[self.case_index];
+        let case = self.timings[self.timing_index][case_index].clone();
+
+        let delay = self.quantized_delay(position);
 
-        println!("TIMING:goto:{}", case.target_music);
-        self.sender
-            .send(PlayerAction::CrossfadeWith(
-                CROSSFADE_TIME_FIRE_EMBLEM,
-                case.target_music,
-            ))
-            .unwrap();
+        log!("TIMING:goto:{}:delay:{:?}", case.target_music, delay);
 
+        self.history.record(
+            self.timing_index,
+            case_index,
+            case.target_music,
+            self.clock.now() - self.started,
+        );
         self.timing_index = case.target_music;
 
-        self.wait_until_next_change();
+        self.wait_until_next_change(self.clock.now() + delay);
+
+        Some(TimerEvent::Transition(Crossfade {
+            target_music: case.target_music,
+            fade: case.fade,
+            delay,
+            stinger: case.stinger,
+        }))
+    }
+
+    /// Time of the clock at which the next tick does something, `None` when the timer only
+    /// waits for a parameter to change.
+    pub fn next_tick_time(&self) -> Option<Duration> {
+        if !self.running {
+            return None;
+        }
+
+        let end = self.time_left.map(|time_left| self.last_tick + time_left);
+        let change = self.case_index.map(|_| self.change_time);
+
+        end.into_iter().chain(change).min()
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Time to wait from `position` for the next bar or beat, zero without a tempo.
+    fn quantized_delay(&self, position: Duration) -> Duration {
+        Duration::from_secs_f64(
+            self.tempo
+                .map(|tempo| tempo.time_to_next_boundary(position.as_secs_f64()))
+                .unwrap_or(0.0),
+        )
     }
 
     pub fn stop(&mut self) {
         self.running = false;
     }
 
-    fn wait_until_next_change(&mut self) {
-        let current = &self.timings[self.timing_index];
-        self.case_index = Self::find_next_case_index(current);
-        let case = &current[self.case_index];
-        let time_to_wait = rng().random_range(case.wait.0..=case.wait.1);
-        println!("TIMING:wait:{}", time_to_wait);
-        self.change_time = Instant::now()
-            .checked_add(Duration::from_millis(time_to_wait as u64))
-            .unwrap();
+    /// Sets the value of a parameter. The next transition is only chosen again when the one
+    /// waiting is no longer available, or when one becomes available while none was, and its
+    /// wait still counts from when the current music or state started.
+    pub fn set_parameter(&mut self, parameter: usize, value: f64) {
+        if parameter >= self.parameters.len() {
+            return;
+        }
+
+        self.parameters[parameter] = value;
+
+        let available = self.available_cases();
+        let choose_again = match self.case_index {
+            Some(case_index) => available.get(case_index) != Some(&true),
+            None => available.contains(&true),
+        };
+        if self.running && choose_again {
+            log!("TIMING:parameters changed the available transitions");
+            self.wait_until_next_change(self.entered);
+        }
     }
 
-    fn find_next_case_index(timing: &Vec<Next>) -> usize {
-        let probability_total = timing.iter().map(|c| c.probability_weight).sum();
-        let mut random_probability = rng().random_range(0..probability_total);
-
-        timing
-            .iter()
-            .position(|c| {
-                if random_probability < c.probability_weight {
-                    true
-                } else {
-                    random_probability -= c.probability_weight;
-                    false
-                }
+    fn available_cases(&self) -> Vec<bool> {
+        self.timings
+            .get(self.timing_index)
+            .map(|current| {
+                current
+                    .iter()
+                    .map(|case| case.is_available(&self.parameters))
+                    .collect()
             })
-            .unwrap()
+            .unwrap_or_default()
+    }
+
+    /// Chooses the next transition, its wait counts from `entered`, when the current music or
+    /// state started.
+    fn wait_until_next_change(&mut self, entered: Duration) {
+        self.entered = entered;
+        let next_change = self.history.choose_with_wait(
+            self.timing_index,
+            self.timings
+                .get(self.timing_index)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            &self.parameters,
+            self.clock.now() - self.started,
+            &mut self.rng,
+        );
+        self.case_index = next_change.map(|(case_index, _)| case_index);
+        let Some((_, time_to_wait)) = next_change else {
+            log!("TIMING:no transition available");
+            return;
+        };
+
+        log!("TIMING:wait:{}", time_to_wait.as_millis());
+        self.change_time = entered + time_to_wait;
     }
 }
 
 fn has_timings(timings: &Vec<Vec<Next>>) -> bool {
     timings.iter().any(|c| !c.is_empty())
 }
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+
+    use crate::core::feusic::test_utils::load;
+
+    use super::*;
+
+    #[test]
+    fn timer_without_transitions_ends_with_the_duration() {
+        let mut timer = FeusicTimer::new(VirtualClock::default(), StdRng::seed_from_u64(0));
+        timer.reset(
+            0,
+            Some(Duration::from_secs(600)),
+            vec![vec![]],
+            None,
+            vec![],
+            vec![false],
+        );
+
+        assert_eq!(timer.next_tick_time(), Some(Duration::from_secs(600)));
+
+        timer.clock_mut().now = Duration::from_secs(599);
+        assert!(timer.tick(Duration::ZERO).is_none());
+
+        timer.clock_mut().now = Duration::from_secs(600);
+        assert!(matches!(
+            timer.tick(Duration::ZERO),
+            Some(TimerEvent::End(delay)) if delay.is_zero()
+        ));
+        assert_eq!(timer.next_tick_time(), None);
+    }
+
+    #[test]
+    fn parameters_keep_the_wait_of_the_transition_still_available() {
+        let feusic = load(
+            &["map.mp3", "battle.mp3", "town.mp3"],
+            r#"
+                start = "map"
+                duration = 600
+
+                [parameters]
+                danger = {}
+
+                [[music]]
+                index = "map"
+                [[music.next]]
+                target = "town"
+                wait_min = 10000
+                weight = 50
+                when = "danger < 0.5"
+                [[music.next]]
+                target = "battle"
+                wait_min = 10000
+                weight = 50
+                when = "danger >= 0.5"
+
+                [[music]]
+                index = "battle"
+                [[music.next]]
+                target = "map"
+                wait_min = 1000
+
+                [[music]]
+                index = "town"
+                [[music.next]]
+                target = "map"
+                wait_min = 1000
+            "#,
+        )
+        .unwrap();
+        let mut timer = FeusicTimer::new(VirtualClock::default(), StdRng::seed_from_u64(0));
+        timer.reset(
+            1,
+            None,
+            feusic.resolved_transitions(&[]),
+            None,
+            vec![0.0],
+            feusic.shuffled_nodes(),
+        );
+
+        // Dragging the slider without changing the available transitions keeps the wait.
+        for (second, danger) in [(2, 0.1), (4, 0.3), (6, 0.4)] {
+            timer.clock_mut().now = Duration::from_secs(second);
+            timer.set_parameter(0, danger);
+            assert_eq!(timer.next_tick_time(), Some(Duration::from_secs(10)));
+        }
+
+        // The town is no longer available, the battle is waited for since the map started.
+        timer.clock_mut().now = Duration::from_secs(8);
+        timer.set_parameter(0, 0.8);
+        assert_eq!(timer.next_tick_time(), Some(Duration::from_secs(10)));
+
+        timer.clock_mut().now = Duration::from_secs(10);
+        assert!(matches!(
+            timer.tick(Duration::ZERO),
+            Some(TimerEvent::Transition(crossfade)) if crossfade.target_music == 0
+        ));
+    }
+}
@@ -4,7 +4,7 @@ use std::{
 };
 
 use crate::core::feusic::{
-    loader::{FeusicMusicLoader, MusicLoader},
+    loader::{is_supported_audio_extension, FeusicMusicLoader, MusicLoader},
     Feusic,
 };
 
@@ -20,34 +20,42 @@ impl FolderPlaylistLoader<FeusicMusicLoader> for BasicFolderPlaylistLoader {
         let files =
             fs::read_dir(folder_path).map_err(|e| format!("Folder path should exist. {}", e))?;
 
-        println!("Loading files from folder {}", folder_path);
+        log!("Loading files from folder {}", folder_path);
 
         files
             .filter_map(|file| {
                 file.inspect_err(|e| eprintln!("Skipping file because {}", e))
                     .ok()
             })
-            .inspect(|file| println!("Checking file {:?}", file.path()))
+            .inspect(|file| log!("Checking file {:?}", file.path()))
             .filter_map(|entry| {
                 let path = entry.path();
                 let extension = path.extension()?.to_str()?;
 
                 if extension == "feusic" && path.is_dir() {
-                    return Some(Feusic::from_feusic_folder(&path));
+                    return Some(
+                        Feusic::from_feusic_folder(&path)
+                            .map_err(|e| format!("Failed to load {:?}. {}", path, e).into()),
+                    );
                 }
 
                 match extension {
-                    "feusic" => Some(Feusic::from_feusic_zip_file(
-                        &path,
-                        &File::open(&path)
-                            .inspect_err(|e| eprintln!("Skipping opening file because {}", e))
-                            .ok()?,
-                    )),
-                    "mp3" | "wav" | "ogg" => Some(Feusic::from_audio_file(&path)),
+                    "feusic" => Some(
+                        Feusic::from_feusic_zip_file(
+                            &path,
+                            &File::open(&path)
+                                .inspect_err(|e| eprintln!("Skipping opening file because {}", e))
+                                .ok()?,
+                        )
+                        .map_err(|e| format!("Failed to load {:?}. {}", path, e).into()),
+                    ),
+                    _ if is_supported_audio_extension(extension) => {
+                        Some(Feusic::from_audio_file(&path))
+                    }
                     _ => return None,
                 }
             })
             .collect::<Result<Vec<_>, _>>()
-            .inspect(|playlist| println!("Playlist of {}", playlist.len()))
+            .inspect(|playlist| log!("Playlist of {}", playlist.len()))
     }
 }
@@ -1,3 +1,4 @@
+mod cli;
 mod core;
 mod ui;
 
@@ -12,6 +13,24 @@ use ui::FilePreferencesHandler;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
+
+    let command: Option<fn(&[String]) -> Result<(), Box<dyn Error>>> =
+        match args.get(1).map(String::as_str) {
+            Some("check") => Some(cli::check::check),
+            Some("graph") => Some(cli::graph::graph),
+            Some("migrate") => Some(cli::migrate::migrate),
+            Some("normalize") => Some(cli::normalize::normalize),
+            Some("pack") => Some(cli::pack::pack),
+            Some("simulate") => Some(cli::simulate::simulate),
+            Some("unpack") => Some(cli::unpack::unpack),
+            _ => None,
+        };
+    if let Some(command) = command {
+        // The commands print their results, the logs of the loaders would get mixed with them.
+        core::set_verbose(false);
+        return command(&args[2..]);
+    }
+
     if args.len() > 1 && args[1] == "--download" {
         println!("--download arg found, downloading.");
         download(args)
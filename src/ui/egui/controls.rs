@@ -1,10 +1,18 @@
 use std::time::Duration;
 
-use egui::{style::HandleShape, Ui};
+use egui::{style::HandleShape, Image, RichText, Ui, Vec2};
 
 use crate::core::{feusic::loader::MusicLoader, player::controller::FeusicPlayerController};
 
-pub(super) fn render<M: MusicLoader>(ui: &mut Ui, player: &FeusicPlayerController<M>) {
+use super::cover::CoverTexture;
+
+const COVER_SIZE: f32 = 96.0;
+
+pub(super) fn render<M: MusicLoader>(
+    ui: &mut Ui,
+    player: &FeusicPlayerController<M>,
+    cover: &mut CoverTexture,
+) {
     ui.ctx().request_repaint_after(Duration::from_millis(200));
 
     let duration = player.music_duration();
@@ -12,9 +20,36 @@ pub(super) fn render<M: MusicLoader>(ui: &mut Ui, player: &FeusicPlayerControlle
     let music_index = player.music_index();
     let mut relative_position = position.as_millis() as f32 / duration.as_millis() as f32;
 
+    ui.horizontal(|ui| {
+        if let Some(texture) = cover.get(ui, player) {
+            ui.add(Image::new((texture.id(), Vec2::splat(COVER_SIZE))));
+        }
+
+        ui.vertical(|ui| {
+            let feusic_index = player.feusic_index();
+            if let Some(title) = player.feusic_titles().get().get(feusic_index) {
+                ui.heading(title);
+            }
+            if let Some(meta) = player.feusic_metas().get().get(feusic_index) {
+                let details = [
+                    meta.game.clone(),
+                    meta.composer.clone(),
+                    meta.year.map(|year| year.to_string()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+
+                if !details.is_empty() {
+                    ui.label(RichText::new(details.join(" · ")).weak());
+                }
+            }
+        });
+    });
+
     ui.vertical_centered(|ui| {
-        let music_names = player.music_names();
-        for (i, name) in music_names.get().iter().enumerate() {
+        let music_titles = player.music_titles();
+        for (i, name) in music_titles.get().iter().enumerate() {
             if i == music_index {
                 ui.style_mut().text_styles.insert(
                     egui::TextStyle::Body,
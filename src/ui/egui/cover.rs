@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use egui::{ColorImage, TextureHandle, TextureOptions, Ui};
+
+use crate::core::{feusic::loader::MusicLoader, player::controller::FeusicPlayerController};
+
+/// Texture of the cover of the playing feusic, decoded again only when the cover changes.
+#[derive(Default)]
+pub(super) struct CoverTexture {
+    bytes: Option<Arc<Vec<u8>>>,
+    texture: Option<TextureHandle>,
+}
+
+impl CoverTexture {
+    pub fn get<M: MusicLoader>(
+        &mut self,
+        ui: &Ui,
+        player: &FeusicPlayerController<M>,
+    ) -> Option<&TextureHandle> {
+        let bytes = player.cover();
+
+        let is_same_cover = match (&bytes, &self.bytes) {
+            (Some(bytes), Some(current)) => Arc::ptr_eq(bytes, current),
+            (None, None) => true,
+            _ => false,
+        };
+
+        if !is_same_cover {
+            self.texture = bytes.as_ref().and_then(|bytes| {
+                image::load_from_memory(bytes)
+                    .inspect_err(|e| eprintln!("Error decoding cover: {}", e))
+                    .ok()
+                    .map(|image| {
+                        let image = image.into_rgba8();
+                        let size = [image.width() as usize, image.height() as usize];
+                        ui.ctx().load_texture(
+                            "cover",
+                            ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+                            TextureOptions::LINEAR,
+                        )
+                    })
+            });
+            self.bytes = bytes;
+        }
+
+        self.texture.as_ref()
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    error::Error,
+    f32::consts::{FRAC_PI_2, TAU},
+    path::PathBuf,
+};
+
+use egui::{
+    Align2, Button, Color32, ComboBox, DragValue, FontId, Id, Key, KeyboardShortcut, Modifiers,
+    Painter, Pos2, Rect, ScrollArea, Sense, Stroke, Ui, Vec2,
+};
+
+use crate::core::{
+    feusic::{
+        loader::FeusicMusicLoader,
+        read_feusic_toml,
+        timing::{Wait, WaitUnit},
+        validation::{Severity, ValidationIssue},
+        write_feusic_toml, Feusic, Next, Start,
+    },
+    log,
+};
+
+const NODE_RADIUS: f32 = 24.0;
+
+const WAIT_UNITS: [(WaitUnit, &str); 5] = [
+    (WaitUnit::Milliseconds, "ms"),
+    (WaitUnit::Seconds, "s"),
+    (WaitUnit::Minutes, "min"),
+    (WaitUnit::Bars, "bars"),
+    (WaitUnit::Loops, "loops"),
+];
+
+const UNDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+const REDO_SHIFT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
+#[derive(Default)]
+pub struct EditorScreen {
+    feusic: Option<EditedFeusic>,
+    error: Option<String>,
+}
+
+/// A feusic open in the editor, with the graph of its musics, or states in layered mode, and
+/// their transitions.
+struct EditedFeusic {
+    path: PathBuf,
+    feusic: Feusic<FeusicMusicLoader>,
+    titles: Vec<String>,
+    graph: Graph,
+    /// The graph as last recorded in the undo history, changes are recorded once the pointer
+    /// is released so a drag is undone at once.
+    committed: Graph,
+    saved: Graph,
+    undo: Vec<Graph>,
+    redo: Vec<Graph>,
+    /// Position of every node in the canvas, from 0 to 1.
+    positions: Vec<Vec2>,
+    selected: Option<usize>,
+    issues: Vec<ValidationIssue>,
+}
+
+/// What the editor changes of a feusic.
+#[derive(Clone, PartialEq)]
+struct Graph {
+    start: Vec<Start>,
+    transitions: Vec<Vec<Next>>,
+}
+
+impl EditorScreen {
+    pub fn render(&mut self, ctx: &mut egui::Ui) {
+        egui::Panel::top("Editor menu").show_inside(ctx, |ui| self.render_menu(ui));
+
+        if let Some(edited) = self.feusic.as_mut() {
+            if !edited.issues.is_empty() {
+                egui::Panel::bottom("Editor issues")
+                    .show_inside(ctx, |ui| edited.render_issues(ui));
+            }
+        }
+
+        egui::CentralPanel::default().show_inside(ctx, |ui| match self.feusic.as_mut() {
+            Some(edited) => edited.render(ui),
+            None => {
+                ui.heading("Editor");
+                ui.label("Open a .feusic folder or file to edit the transitions of its musics.");
+            }
+        });
+    }
+
+    fn render_menu(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Open folder…").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    self.open(path);
+                }
+            }
+
+            if ui.button("Open file…").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Feusic", &["feusic"])
+                    .pick_file()
+                {
+                    self.open(path);
+                }
+            }
+
+            let Some(edited) = self.feusic.as_mut() else {
+                return;
+            };
+
+            ui.separator();
+
+            if ui
+                .add_enabled(!edited.undo.is_empty(), Button::new("Undo"))
+                .clicked()
+            {
+                edited.undo();
+            }
+
+            if ui
+                .add_enabled(!edited.redo.is_empty(), Button::new("Redo"))
+                .clicked()
+            {
+                edited.redo();
+            }
+
+            let has_errors = edited
+                .issues
+                .iter()
+                .any(|issue| issue.severity == Severity::Error);
+            if ui
+                .add_enabled(
+                    edited.graph != edited.saved && !has_errors,
+                    Button::new("Save"),
+                )
+                .clicked()
+            {
+                self.error = edited
+                    .save()
+                    .err()
+                    .map(|e| format!("Cannot save {}: {}", edited.path.display(), e));
+            }
+
+            ui.separator();
+
+            let unsaved = if edited.graph != edited.saved {
+                " *"
+            } else {
+                ""
+            };
+            ui.label(format!("{}{}", edited.feusic.display_name(), unsaved));
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    fn open(&mut self, path: PathBuf) {
+        match Feusic::from_feusic_path(&path) {
+            Ok(feusic) => {
+                self.feusic = Some(EditedFeusic::new(path, feusic));
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("Cannot open {}: {}", path.display(), e)),
+        }
+    }
+}
+
+impl EditedFeusic {
+    fn new(path: PathBuf, feusic: Feusic<FeusicMusicLoader>) -> Self {
+        let graph = Graph {
+            start: feusic.start.clone(),
+            transitions: feusic.transitions(),
+        };
+        let nodes_count = graph.transitions.len();
+
+        // Nodes start on a circle, clockwise from the top.
+        let positions = (0..nodes_count)
+            .map(|node| {
+                let angle = TAU * node as f32 / nodes_count as f32 - FRAC_PI_2;
+                Vec2::splat(0.5) + Vec2::angled(angle) * 0.38
+            })
+            .collect();
+
+        Self {
+            path,
+            titles: feusic.node_titles(),
+            issues: feusic.validate(),
+            feusic,
+            committed: graph.clone(),
+            saved: graph.clone(),
+            graph,
+            undo: vec![],
+            redo: vec![],
+            positions,
+            selected: None,
+        }
+    }
+
+    fn render(&mut self, ui: &mut Ui) {
+        // Redo first, the undo shortcut would also match with shift.
+        if ui
+            .input_mut(|input| input.consume_shortcut(&REDO) || input.consume_shortcut(&REDO_SHIFT))
+        {
+            self.redo();
+        }
+        if ui.input_mut(|input| input.consume_shortcut(&UNDO)) {
+            self.undo();
+        }
+
+        ui.columns(2, |columns| {
+            self.render_graph(&mut columns[0]);
+            self.render_selected(&mut columns[1]);
+        });
+
+        self.commit(ui);
+    }
+
+    fn render_graph(&mut self, ui: &mut Ui) {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click());
+        let canvas = response.rect;
+        if response.clicked() {
+            self.selected = None;
+        }
+
+        let centers = self
+            .positions
+            .iter()
+            .map(|position| canvas.min + *position * canvas.size())
+            .collect::<Vec<Pos2>>();
+
+        let visuals = ui.visuals();
+        let text_color = visuals.text_color();
+        let edge_color = visuals.weak_text_color();
+        let selection_color = visuals.selection.bg_fill;
+        let node_fill = visuals.widgets.inactive.bg_fill;
+
+        for (node, next_choices) in self.graph.transitions.iter().enumerate() {
+            for next in next_choices {
+                let Some(target_center) = centers.get(next.target_music) else {
+                    continue;
+                };
+                let color = if self.selected == Some(node) {
+                    selection_color
+                } else {
+                    edge_color
+                };
+                draw_edge(
+                    &painter,
+                    centers[node],
+                    *target_center,
+                    node == next.target_music,
+                    Stroke::new(1.5, color),
+                );
+            }
+        }
+
+        for (node, center) in centers.iter().enumerate() {
+            let node_rect = Rect::from_center_size(*center, Vec2::splat(NODE_RADIUS * 2.0));
+            let node_response = ui.interact(
+                node_rect,
+                Id::new(("editor node", node)),
+                Sense::click_and_drag(),
+            );
+            if node_response.clicked() || node_response.drag_started() {
+                self.selected = Some(node);
+            }
+            if node_response.dragged() {
+                self.positions[node] = (self.positions[node]
+                    + node_response.drag_delta() / canvas.size())
+                .clamp(Vec2::ZERO, Vec2::splat(1.0));
+            }
+
+            let fill = if self.selected == Some(node) {
+                selection_color
+            } else {
+                node_fill
+            };
+            // Start nodes have a thicker outline.
+            let is_start = self.graph.start.iter().any(|start| start.node == node);
+            let stroke = Stroke::new(if is_start { 3.0 } else { 1.0 }, text_color);
+
+            painter.circle(*center, NODE_RADIUS, fill, stroke);
+            painter.text(
+                *center,
+                Align2::CENTER_CENTER,
+                node.to_string(),
+                FontId::proportional(14.0),
+                text_color,
+            );
+            painter.text(
+                *center + Vec2::new(0.0, NODE_RADIUS + 4.0),
+                Align2::CENTER_TOP,
+                &self.titles[node],
+                FontId::proportional(12.0),
+                text_color,
+            );
+        }
+    }
+
+    fn render_selected(&mut self, ui: &mut Ui) {
+        let Some(node) = self.selected else {
+            ui.label("Select a node to edit its transitions, drag nodes to move them.");
+            return;
+        };
+
+        ui.heading(format!("{} {}", node, self.titles[node]));
+
+        let is_only_start = matches!(self.graph.start.as_slice(), [start] if start.node == node);
+        if ui
+            .add_enabled(!is_only_start, Button::new("Set as start"))
+            .clicked()
+        {
+            self.graph.start = vec![Start {
+                node,
+                probability_weight: 100,
+            }];
+        }
+
+        ui.separator();
+
+        let titles = &self.titles;
+        let mut removed = None;
+
+        ScrollArea::vertical().show(ui, |ui| {
+            for (case_index, next) in self.graph.transitions[node].iter_mut().enumerate() {
+                ui.push_id(case_index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("To");
+                        ComboBox::from_id_salt("target")
+                            .selected_text(
+                                titles
+                                    .get(next.target_music)
+                                    .map(|title| title.as_str())
+                                    .unwrap_or("?"),
+                            )
+                            .show_ui(ui, |ui| {
+                                for (target, title) in titles.iter().enumerate() {
+                                    ui.selectable_value(&mut next.target_music, target, title);
+                                }
+                            });
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(case_index);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Wait");
+                        render_wait(ui, "wait min", &mut next.wait.0);
+                        ui.label("to");
+                        render_wait(ui, "wait max", &mut next.wait.1);
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Weight");
+                        ui.add(DragValue::new(&mut next.probability_weight).range(1..=1000));
+                    });
+
+                    ui.separator();
+                });
+            }
+
+            if ui.button("Add transition").clicked() {
+                self.graph.transitions[node].push(Next {
+                    probability_weight: 100,
+                    target_music: node,
+                    wait: (Wait::millis(60000), Wait::millis(60000)),
+                    fade: self.feusic.fade,
+                    stinger: None,
+                    when: None,
+                    constraints: Default::default(),
+                });
+            }
+        });
+
+        if let Some(case_index) = removed {
+            self.graph.transitions[node].remove(case_index);
+        }
+    }
+
+    fn render_issues(&self, ui: &mut Ui) {
+        for issue in self.issues.iter() {
+            let color = match issue.severity {
+                Severity::Error => Color32::RED,
+                Severity::Warning => Color32::ORANGE,
+            };
+            ui.colored_label(color, issue.to_string());
+        }
+    }
+
+    /// Records the changes of the graph in the undo history once they are done.
+    fn commit(&mut self, ui: &Ui) {
+        if self.graph == self.committed || ui.input(|input| input.pointer.any_down()) {
+            return;
+        }
+
+        self.undo
+            .push(std::mem::replace(&mut self.committed, self.graph.clone()));
+        self.redo.clear();
+        self.validate();
+    }
+
+    fn undo(&mut self) {
+        if let Some(graph) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.graph, graph));
+            self.committed = self.graph.clone();
+            self.validate();
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(graph) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.graph, graph));
+            self.committed = self.graph.clone();
+            self.validate();
+        }
+    }
+
+    /// Applies the graph to the feusic and validates it.
+    fn validate(&mut self) {
+        self.feusic.start = self.graph.start.clone();
+        self.feusic.set_transitions(self.graph.transitions.clone());
+        self.issues = self.feusic.validate();
+    }
+
+    /// Writes the changes of the graph since the last save to the feusic.toml, in its folder
+    /// or zip file. The rest of the file is kept as it is.
+    fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.validate();
+        let (feusic_toml, _) = read_feusic_toml(&self.path)?;
+        let feusic_toml = self.feusic.edit_feusic_toml(
+            &feusic_toml,
+            &self.saved.start,
+            &self.saved.transitions,
+        )?;
+        write_feusic_toml(&self.path, &feusic_toml)?;
+        self.saved = self.graph.clone();
+        log!("Saved {}", self.path.display());
+
+        Ok(())
+    }
+}
+
+/// Draws the arrow of a transition between two nodes, moved to its side so the transitions
+/// going both ways between two nodes do not overlap. A node going to itself gets a loop above.
+fn draw_edge(painter: &Painter, from: Pos2, to: Pos2, is_loop: bool, stroke: Stroke) {
+    if is_loop {
+        painter.circle_stroke(
+            from - Vec2::new(0.0, NODE_RADIUS),
+            NODE_RADIUS * 0.6,
+            stroke,
+        );
+        return;
+    }
+
+    let direction = (to - from).normalized();
+    let side = direction.rot90() * 6.0;
+    let start = from + direction * NODE_RADIUS + side;
+    let end = to - direction * NODE_RADIUS + side;
+    painter.arrow(start, end - start, stroke);
+}
+
+fn render_wait(ui: &mut Ui, id: &str, wait: &mut Wait) {
+    let speed = match wait.unit {
+        WaitUnit::Milliseconds => 100.0,
+        _ => 0.1,
+    };
+    ui.add(DragValue::new(&mut wait.amount).speed(speed));
+
+    let unit_label = WAIT_UNITS
+        .iter()
+        .find(|(unit, _)| *unit == wait.unit)
+        .map(|(_, label)| *label)
+        .unwrap_or("?");
+    ComboBox::from_id_salt(id)
+        .width(60.0)
+        .selected_text(unit_label)
+        .show_ui(ui, |ui| {
+            for (unit, label) in WAIT_UNITS {
+                ui.selectable_value(&mut wait.unit, unit, label);
+            }
+        });
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use egui::Ui;
+use egui::{Color32, RichText, Ui};
 
 use crate::{
     core::{
@@ -10,13 +10,13 @@ use crate::{
     ui::{Preferences, PreferencesHandler},
 };
 
-
 pub(super) fn render<M: MusicLoader>(
     ui: &mut Ui,
     player: &FeusicPlayerController<M>,
     playlist_loader: &impl FolderPlaylistLoader<M>,
     preferences: &mut Preferences,
     preferences_handler: &impl PreferencesHandler,
+    load_error: &mut Option<String>,
 ) {
     ui.horizontal(|ui| {
         if ui.button("Crossfade").clicked() {
@@ -34,6 +34,7 @@ pub(super) fn render<M: MusicLoader>(
             if let Some(path) = rfd::FileDialog::new().pick_folder() {
                 match playlist_loader.load(path.to_str().unwrap()) {
                     Ok(playlist) => {
+                        *load_error = None;
                         preferences.last_playlist_path = Some(path.to_str().unwrap().to_string());
                         preferences_handler.save_preferences(preferences);
 
@@ -46,7 +47,7 @@ pub(super) fn render<M: MusicLoader>(
                         if was_paused {
                             player.resume();
                         }
-                        eprintln!("Error loading playlist: {}", e);
+                        *load_error = Some(e.to_string());
                     }
                 }
             } else {
@@ -56,4 +57,15 @@ pub(super) fn render<M: MusicLoader>(
             }
         }
     });
+
+    if let Some(error) = load_error.clone() {
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.colored_label(Color32::RED, "Failed to load playlist");
+            if ui.button("Dismiss").clicked() {
+                *load_error = None;
+            }
+        });
+        ui.label(RichText::new(error).monospace().color(Color32::RED));
+    }
 }
@@ -1,3 +1,5 @@
+use cover::CoverTexture;
+use editor_screen::EditorScreen;
 use egui::IconData;
 use youtube_screen::YoutubeScreen;
 
@@ -10,7 +12,10 @@ use std::error::Error;
 use super::{Preferences, PreferencesHandler};
 
 mod controls;
+mod cover;
+mod editor_screen;
 mod extras;
+mod parameters;
 mod playlist;
 mod tabs;
 mod youtube_screen;
@@ -23,14 +28,18 @@ struct FeusicEguiApp<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: Preferences
     playlist_loader: P,
     preferences_handler: PH,
     preferences: Preferences,
+    load_error: Option<String>,
+    cover: CoverTexture,
 
     youtube_screen: Option<YoutubeScreen>,
+    editor_screen: EditorScreen,
     screen: FeusicEguiScreen,
 }
 
 enum FeusicEguiScreen {
     Main,
     Youtube,
+    Editor,
 }
 
 impl<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler> eframe::App
@@ -65,6 +74,7 @@ impl<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler> eframe:
                     screen.render(ctx)
                 }
             }
+            FeusicEguiScreen::Editor => self.editor_screen.render(ctx),
         }
     }
 }
@@ -78,10 +88,12 @@ impl<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler> FeusicE
                 &self.playlist_loader,
                 &mut self.preferences,
                 &self.preferences_handler,
+                &mut self.load_error,
             );
         });
         egui::Panel::bottom("Player controls").show_inside(ctx, |ui| {
-            controls::render(ui, &self.player);
+            parameters::render(ui, &self.player);
+            controls::render(ui, &self.player, &mut self.cover);
             ui.add_space(5.0);
         });
         egui::CentralPanel::default().show_inside(ctx, |ui| {
@@ -96,6 +108,7 @@ pub fn run_ui<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler
     playlist_loader: P,
     preferences: Preferences,
     preferences_handler: PH,
+    load_error: Option<String>,
 ) -> Result<(), Box<dyn Error>> {
     let (icon_rgba, icon_width, icon_height) = {
         let image = image::load_from_memory(ICON)
@@ -127,7 +140,10 @@ pub fn run_ui<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler
                 playlist_loader,
                 preferences,
                 preferences_handler,
+                load_error,
+                cover: CoverTexture::default(),
                 youtube_screen: None,
+                editor_screen: EditorScreen::default(),
                 screen: FeusicEguiScreen::Main,
             }))
         }),
@@ -0,0 +1,32 @@
+use egui::{Id, Slider, Ui};
+
+use crate::core::{feusic::loader::MusicLoader, player::controller::FeusicPlayerController};
+
+pub(super) fn render<M: MusicLoader>(ui: &mut Ui, player: &FeusicPlayerController<M>) {
+    let parameters = player.parameters();
+    let values = player.parameter_values();
+
+    if parameters.get().is_empty() {
+        return;
+    }
+
+    ui.horizontal_wrapped(|ui| {
+        for (parameter, value) in parameters.get().iter().zip(values.get().iter()) {
+            // The value is only sent to the player when the slider is released, every value
+            // sent can change the transition the player waits for.
+            let id = Id::new(("parameter", &parameter.name));
+            let mut value = ui.data(|data| data.get_temp::<f64>(id)).unwrap_or(*value);
+            ui.label(&parameter.name);
+            let response = ui.add(Slider::new(&mut value, parameter.min..=parameter.max));
+            if response.changed() {
+                ui.data_mut(|data| data.insert_temp(id, value));
+            }
+            if response.drag_stopped() || response.lost_focus() {
+                ui.data_mut(|data| data.remove::<f64>(id));
+                player.set_parameter(&parameter.name, value);
+            }
+        }
+    });
+
+    ui.separator();
+}
@@ -1,4 +1,4 @@
-use egui::{Label, Ui};
+use egui::{Label, RichText, Ui};
 use egui_extras::{Column, TableBuilder};
 
 use crate::core::{feusic::loader::MusicLoader, player::controller::FeusicPlayerController};
@@ -7,13 +7,16 @@ pub(super) fn render<M: MusicLoader>(ui: &mut Ui, player: &FeusicPlayerControlle
     TableBuilder::new(ui)
         .column(Column::auto())
         .column(Column::remainder())
+        .column(Column::auto())
         .sense(egui::Sense::click())
         .body(|body| {
             let feusic_index = player.feusic_index();
-            let feusic_names_ref = player.feusic_names();
-            let feusic_names = feusic_names_ref.get();
+            let feusic_titles_ref = player.feusic_titles();
+            let feusic_titles = feusic_titles_ref.get();
+            let feusic_metas_ref = player.feusic_metas();
+            let feusic_metas = feusic_metas_ref.get();
 
-            body.rows(18.0, feusic_names.len(), |mut row| {
+            body.rows(18.0, feusic_titles.len(), |mut row| {
                 let index = row.index();
                 row.set_selected(index == feusic_index);
 
@@ -22,7 +25,13 @@ pub(super) fn render<M: MusicLoader>(ui: &mut Ui, player: &FeusicPlayerControlle
                 });
 
                 row.col(|ui| {
-                    ui.add(Label::new(feusic_names[index].clone()).selectable(false));
+                    ui.add(Label::new(feusic_titles[index].clone()).selectable(false));
+                });
+
+                row.col(|ui| {
+                    if let Some(game) = feusic_metas.get(index).and_then(|m| m.game.clone()) {
+                        ui.add(Label::new(RichText::new(game).weak()).selectable(false));
+                    }
                 });
 
                 if row.response().double_clicked() {
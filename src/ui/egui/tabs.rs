@@ -21,6 +21,13 @@ pub(super) fn render(ui: &mut Ui, screen: &FeusicEguiScreen) -> Option<FeusicEgu
         {
             new_screen = Some(FeusicEguiScreen::Youtube);
         }
+
+        if ui
+            .selectable_label(matches!(screen, FeusicEguiScreen::Editor), "Editor")
+            .clicked()
+        {
+            new_screen = Some(FeusicEguiScreen::Editor);
+        }
     });
 
     new_screen
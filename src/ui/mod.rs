@@ -24,16 +24,26 @@ pub fn run_ui<M: MusicLoader, P: FolderPlaylistLoader<M>, PH: PreferencesHandler
     preferences_handler: PH,
 ) -> Result<(), Box<dyn Error>> {
     let preferences = preferences_handler.load_preferences();
+    let mut load_error = None;
     if let Some(ref playlist_path) = preferences.last_playlist_path {
-        player.set_playlist(playlist_loader.load(playlist_path.as_str())?);
-        player.play();
+        match playlist_loader.load(playlist_path.as_str()) {
+            Ok(playlist) => {
+                player.set_playlist(playlist);
+                player.play();
+            }
+            Err(e) => load_error = Some(e.to_string()),
+        }
     }
 
     match ui {
-        FeusicPlayerUi::Egui => {
-            egui::run_ui(player, playlist_loader, preferences, preferences_handler)
-        }
-        FeusicPlayerUi::Terminal => terminal::run_ui(player),
+        FeusicPlayerUi::Egui => egui::run_ui(
+            player,
+            playlist_loader,
+            preferences,
+            preferences_handler,
+            load_error,
+        ),
+        FeusicPlayerUi::Terminal => terminal::run_ui(player, load_error),
     }
 }
 
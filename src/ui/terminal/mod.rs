@@ -2,7 +2,14 @@ use std::{error::Error, io::Write};
 
 use crate::core::{feusic::loader::MusicLoader, player::controller::FeusicPlayerController};
 
-pub fn run_ui<M: MusicLoader>(player: FeusicPlayerController<M>) -> Result<(), Box<dyn Error>> {
+pub fn run_ui<M: MusicLoader>(
+    player: FeusicPlayerController<M>,
+    load_error: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(error) = load_error {
+        println!("Failed to load playlist:\n{}", error);
+    }
+
     loop {
         println!("Commands: pause, resume, stop, loop, crossfade, next, exit");
         std::io::stdout().flush()?;